@@ -0,0 +1,161 @@
+// Compares the integer serialization of the builder against writing the same
+// arrays through core::fmt, which is what the primitive impls used to do.
+// The digits rows compare only the number formatting, both writing through
+// a &mut dyn Write like the builder does.
+// Run with: cargo bench --bench integers
+
+extern crate json_builder;
+
+use std::hint::black_box;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use json_builder::{JSONBuilder, Integer, write_integer};
+
+const ROUNDS: usize = 200;
+
+fn measure<F: FnMut() -> usize>(mut f: F) -> Duration {
+	// warm up
+	black_box(f());
+	let start = Instant::now();
+	for _ in 0..ROUNDS {
+		black_box(f());
+	}
+	start.elapsed() / ROUNDS as u32
+}
+
+fn fmt_array<T: std::fmt::Display>(items: &[T], out: &mut Vec<u8>) {
+	out.push(b'[');
+	for (i, item) in items.iter().enumerate() {
+		if i > 0 {
+			out.push(b',');
+		}
+		write!(out, "{}", item).unwrap();
+	}
+	out.push(b']');
+}
+
+fn digits_integer<T: Integer>(items: &[T], out: &mut dyn Write) {
+	for item in items {
+		write_integer(*item, out).ok().unwrap();
+	}
+}
+
+fn digits_fmt<T: std::fmt::Display>(items: &[T], out: &mut dyn Write) {
+	for item in items {
+		write!(out, "{}", item).unwrap();
+	}
+}
+
+fn report(name: &str, elements: usize, json_builder: Duration, fmt: Duration) {
+	println!("{:<20} json_builder: {:>7.2} ns/elem   core::fmt: {:>7.2} ns/elem   speedup: {:.2}x",
+		name,
+		json_builder.as_nanos() as f64 / elements as f64,
+		fmt.as_nanos() as f64 / elements as f64,
+		fmt.as_secs_f64() / json_builder.as_secs_f64());
+}
+
+fn bench_vec_u64() {
+	let mut state = 0x2545F4914F6CDD1Du64;
+	let values: Vec<u64> = (0..100_000).map(|i| {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		state >> (i % 64)
+	}).collect();
+	let mut out = Vec::with_capacity(values.len() * 21);
+
+	let builder = measure(|| {
+		out.clear();
+		{
+			let mut builder = JSONBuilder::new(&mut out);
+			builder.value(&values).ok().unwrap();
+		}
+		out.len()
+	});
+
+	let fmt = measure(|| {
+		out.clear();
+		fmt_array(&values, &mut out);
+		out.len()
+	});
+
+	report("Vec<u64>", values.len(), builder, fmt);
+
+	let integer = measure(|| {
+		out.clear();
+		digits_integer(&values, &mut out);
+		out.len()
+	});
+
+	let fmt = measure(|| {
+		out.clear();
+		digits_fmt(&values, &mut out);
+		out.len()
+	});
+
+	report("Vec<u64> digits", values.len(), integer, fmt);
+}
+
+fn bench_i32_arrays() {
+	let arrays: Vec<[i32; 32]> = (0..4_000).map(|i| {
+		let mut array = [0i32; 32];
+		for (j, item) in array.iter_mut().enumerate() {
+			*item = (i * 7919 + j as i32 * 104_729) * if j % 2 == 0 { 1 } else { -1 };
+		}
+		array
+	}).collect();
+	let elements = arrays.len() * 32;
+	let mut out = Vec::with_capacity(elements * 12);
+
+	let builder = measure(|| {
+		out.clear();
+		{
+			let mut builder = JSONBuilder::new(&mut out);
+			builder.begin_array().ok().unwrap();
+			for array in &arrays {
+				builder.value(array).ok().unwrap();
+			}
+			builder.end_array().ok().unwrap();
+		}
+		out.len()
+	});
+
+	let fmt = measure(|| {
+		out.clear();
+		out.push(b'[');
+		for (i, array) in arrays.iter().enumerate() {
+			if i > 0 {
+				out.push(b',');
+			}
+			fmt_array(array, &mut out);
+		}
+		out.push(b']');
+		out.len()
+	});
+
+	report("[i32; 32]", elements, builder, fmt);
+
+	let integer = measure(|| {
+		out.clear();
+		for array in &arrays {
+			digits_integer(array, &mut out);
+		}
+		out.len()
+	});
+
+	let fmt = measure(|| {
+		out.clear();
+		for array in &arrays {
+			digits_fmt(array, &mut out);
+		}
+		out.len()
+	});
+
+	report("[i32; 32] digits", elements, integer, fmt);
+}
+
+fn main() {
+	bench_vec_u64();
+	bench_i32_arrays();
+}
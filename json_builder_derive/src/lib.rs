@@ -0,0 +1,368 @@
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, ExprPath};
+
+#[proc_macro_derive(IntoJSON, attributes(json))]
+pub fn derive_into_json(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match expand_into_json(&input) {
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.to_compile_error().into()
+	}
+}
+
+#[derive(Copy, Clone)]
+enum RenameRule {
+	Lower,
+	Upper,
+	Pascal,
+	Camel,
+	Snake,
+	ScreamingSnake,
+	Kebab,
+	ScreamingKebab
+}
+
+impl RenameRule {
+	fn from_str(rule: &str) -> Option<RenameRule> {
+		match rule {
+			"lowercase"            => Some(RenameRule::Lower),
+			"UPPERCASE"            => Some(RenameRule::Upper),
+			"PascalCase"           => Some(RenameRule::Pascal),
+			"camelCase"            => Some(RenameRule::Camel),
+			"snake_case"           => Some(RenameRule::Snake),
+			"SCREAMING_SNAKE_CASE" => Some(RenameRule::ScreamingSnake),
+			"kebab-case"           => Some(RenameRule::Kebab),
+			"SCREAMING-KEBAB-CASE" => Some(RenameRule::ScreamingKebab),
+			_ => None
+		}
+	}
+
+	// field names are expected to be snake_case, as is the Rust convention
+	fn apply(self, name: &str) -> String {
+		match self {
+			RenameRule::Lower => name.to_lowercase(),
+			RenameRule::Upper => name.to_uppercase(),
+			RenameRule::Snake => name.to_string(),
+			RenameRule::ScreamingSnake => name.to_uppercase(),
+			RenameRule::Kebab => name.replace('_', "-"),
+			RenameRule::ScreamingKebab => name.replace('_', "-").to_uppercase(),
+			RenameRule::Pascal | RenameRule::Camel => {
+				let mut renamed = String::with_capacity(name.len());
+				let pascal = matches!(self, RenameRule::Pascal);
+				let mut upper = pascal;
+				for c in name.chars() {
+					if c == '_' {
+						// leading underscores don't start a word in camelCase
+						upper = pascal || !renamed.is_empty();
+					} else if upper {
+						renamed.extend(c.to_uppercase());
+						upper = false;
+					} else {
+						renamed.push(c);
+					}
+				}
+				renamed
+			}
+		}
+	}
+}
+
+struct ContainerAttrs {
+	rename_all: Option<RenameRule>
+}
+
+struct FieldAttrs {
+	rename: Option<String>,
+	skip: bool,
+	skip_if: Option<ExprPath>,
+	with: Option<ExprPath>
+}
+
+fn parse_container_attrs(input: &DeriveInput) -> syn::Result<ContainerAttrs> {
+	let mut attrs = ContainerAttrs { rename_all: None };
+	for attr in &input.attrs {
+		if !attr.path().is_ident("json") {
+			continue;
+		}
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("rename_all") {
+				let rule: LitStr = meta.value()?.parse()?;
+				match RenameRule::from_str(&rule.value()) {
+					Some(rule) => attrs.rename_all = Some(rule),
+					None => return Err(syn::Error::new_spanned(&rule, "unknown rename_all rule"))
+				}
+				Ok(())
+			} else {
+				Err(meta.error("unknown json container attribute"))
+			}
+		})?;
+	}
+	Ok(attrs)
+}
+
+fn parse_field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+	let mut attrs = FieldAttrs { rename: None, skip: false, skip_if: None, with: None };
+	for attr in &field.attrs {
+		if !attr.path().is_ident("json") {
+			continue;
+		}
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("rename") {
+				let name: LitStr = meta.value()?.parse()?;
+				attrs.rename = Some(name.value());
+			} else if meta.path.is_ident("skip") {
+				attrs.skip = true;
+			} else if meta.path.is_ident("skip_if") {
+				let path: LitStr = meta.value()?.parse()?;
+				attrs.skip_if = Some(path.parse()?);
+			} else if meta.path.is_ident("with") {
+				let path: LitStr = meta.value()?.parse()?;
+				attrs.with = Some(path.parse()?);
+			} else {
+				return Err(meta.error("unknown json field attribute"));
+			}
+			Ok(())
+		})?;
+	}
+	Ok(attrs)
+}
+
+fn field_name(ident: &Ident) -> String {
+	let name = ident.to_string();
+	match name.strip_prefix("r#") {
+		Some(name) => name.to_string(),
+		None => name
+	}
+}
+
+// Writes a single value, either through the builder or through a custom
+// `with` function. Those take the builder of into_json(), so they can be
+// written like IntoJSON implementations.
+fn write_value(attrs: &FieldAttrs, access: &TokenStream2) -> TokenStream2 {
+	match attrs.with {
+		Some(ref with) => quote! { builder.with_boxed(|builder| #with(&#access, builder))?; },
+		None => quote! { builder.value(&#access)?; }
+	}
+}
+
+fn skip_if(attrs: &FieldAttrs, access: &TokenStream2, body: TokenStream2) -> TokenStream2 {
+	match attrs.skip_if {
+		Some(ref skip_if) => quote! {
+			if !#skip_if(&#access) {
+				#body
+			}
+		},
+		None => body
+	}
+}
+
+fn mentions(tokens: TokenStream2, ident: &Ident) -> bool {
+	tokens.into_iter().any(|token| match token {
+		TokenTree::Ident(ref other) => other == ident,
+		TokenTree::Group(ref group) => mentions(group.stream(), ident),
+		_ => false
+	})
+}
+
+fn expand_into_json(input: &DeriveInput) -> syn::Result<TokenStream2> {
+	let container = parse_container_attrs(input)?;
+
+	let data = match input.data {
+		Data::Struct(ref data) => data,
+		_ => return Err(syn::Error::new_spanned(input, "IntoJSON can only be derived for structs"))
+	};
+
+	// types of the fields that are written through IntoJSON
+	let mut value_types = Vec::new();
+	let body = match data.fields {
+		Fields::Named(ref fields) => {
+			let mut items = Vec::new();
+			for field in &fields.named {
+				let attrs = parse_field_attrs(field)?;
+				if attrs.skip {
+					continue;
+				}
+				if attrs.with.is_none() {
+					value_types.push(&field.ty);
+				}
+				let ident = field.ident.as_ref().unwrap();
+				let key = match attrs.rename {
+					Some(ref rename) => rename.clone(),
+					None => match container.rename_all {
+						Some(rule) => rule.apply(&field_name(ident)),
+						None => field_name(ident)
+					}
+				};
+				let access = quote! { self.#ident };
+				let value = write_value(&attrs, &access);
+				items.push(skip_if(&attrs, &access, quote! {
+					builder.key(#key)?;
+					#value
+				}));
+			}
+			quote! {
+				builder.begin_object()?;
+				#(#items)*
+				builder.end_object()
+			}
+		},
+
+		Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => {
+			let field = &fields.unnamed[0];
+			let attrs = parse_field_attrs(field)?;
+			if attrs.rename.is_some() || attrs.skip || attrs.skip_if.is_some() {
+				return Err(syn::Error::new_spanned(field, "newtype fields only support the with attribute"));
+			}
+			match attrs.with {
+				Some(ref with) => quote! { builder.with_boxed(|builder| #with(&self.0, builder)) },
+				None => {
+					value_types.push(&field.ty);
+					quote! { builder.value(&self.0) }
+				}
+			}
+		},
+
+		Fields::Unnamed(ref fields) => {
+			let mut items = Vec::new();
+			for (index, field) in fields.unnamed.iter().enumerate() {
+				let attrs = parse_field_attrs(field)?;
+				if attrs.rename.is_some() {
+					return Err(syn::Error::new_spanned(field, "tuple fields cannot be renamed"));
+				}
+				if attrs.skip {
+					continue;
+				}
+				if attrs.with.is_none() {
+					value_types.push(&field.ty);
+				}
+				let index = syn::Index::from(index);
+				let access = quote! { self.#index };
+				items.push(skip_if(&attrs, &access, write_value(&attrs, &access)));
+			}
+			quote! {
+				builder.begin_array()?;
+				#(#items)*
+				builder.end_array()
+			}
+		},
+
+		Fields::Unit => quote! { builder.null() }
+	};
+
+	let mut generics = input.generics.clone();
+	{
+		let where_clause = generics.make_where_clause();
+		// skipped fields and those written by a `with` function don't need IntoJSON
+		for param in input.generics.type_params() {
+			let ident = &param.ident;
+			if value_types.iter().any(|ty| mentions(quote! { #ty }, ident)) {
+				where_clause.predicates.push(parse_quote! { #ident: ::json_builder::IntoJSON });
+			}
+		}
+	}
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let name = &input.ident;
+
+	Ok(quote! {
+		impl #impl_generics ::json_builder::IntoJSON for #name #ty_generics #where_clause {
+			fn into_json(&self, builder: &mut ::json_builder::JSONBuilder) -> ::json_builder::Result {
+				::json_builder::IntoJSON::write_json(self, builder)
+			}
+
+			fn write_json<__W: ::json_builder::Sink>(&self, builder: &mut ::json_builder::JSONBuilder<__W>) -> ::json_builder::Result {
+				#body
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(input: DeriveInput) -> String {
+		expand_into_json(&input).unwrap_err().to_string()
+	}
+
+	// the where clause of the impl, which is followed by its body
+	fn bounds(input: DeriveInput) -> String {
+		let tokens: Vec<TokenTree> = expand_into_json(&input).unwrap().into_iter().collect();
+		let start = tokens.iter().position(|token| matches!(token, TokenTree::Ident(ident) if ident == "where")).unwrap();
+		let end = tokens.iter().position(|token| matches!(token, TokenTree::Group(_))).unwrap();
+		tokens[start + 1..end].iter().cloned().collect::<TokenStream2>().to_string()
+	}
+
+	#[test]
+	fn renames() {
+		let rules = [
+			("lowercase", "some_field_2"),
+			("UPPERCASE", "SOME_FIELD_2"),
+			("PascalCase", "SomeField2"),
+			("camelCase", "someField2"),
+			("snake_case", "some_field_2"),
+			("SCREAMING_SNAKE_CASE", "SOME_FIELD_2"),
+			("kebab-case", "some-field-2"),
+			("SCREAMING-KEBAB-CASE", "SOME-FIELD-2")
+		];
+		for (rule, expected) in rules {
+			assert_eq!(RenameRule::from_str(rule).unwrap().apply("some_field_2"), expected);
+		}
+		assert_eq!(RenameRule::Pascal.apply("_private_field"), "PrivateField");
+		assert_eq!(RenameRule::Camel.apply("_private_field"), "privateField");
+		assert_eq!(RenameRule::Camel.apply("__a__b"), "aB");
+		assert!(RenameRule::from_str("Title Case").is_none());
+	}
+
+	#[test]
+	fn rejects_unknown_attributes() {
+		assert_eq!(error(parse_quote! {
+			#[json(rename = "x")]
+			struct A { a: u8 }
+		}), "unknown json container attribute");
+		assert_eq!(error(parse_quote! {
+			#[json(rename_all = "Title Case")]
+			struct A { a: u8 }
+		}), "unknown rename_all rule");
+		assert_eq!(error(parse_quote! {
+			struct A { #[json(flatten)] a: u8 }
+		}), "unknown json field attribute");
+		assert_eq!(error(parse_quote! {
+			struct A(#[json(skip)] u8);
+		}), "newtype fields only support the with attribute");
+		assert_eq!(error(parse_quote! {
+			struct A(#[json(rename = "a")] u8, u8);
+		}), "tuple fields cannot be renamed");
+		assert_eq!(error(parse_quote! {
+			enum A { B }
+		}), "IntoJSON can only be derived for structs");
+	}
+
+	#[test]
+	fn bounds_only_written_type_params() {
+		assert_eq!(bounds(parse_quote! {
+			struct A<T, U, V, W> {
+				t: Vec<T>,
+				#[json(skip)]
+				u: U,
+				#[json(with = "write_v")]
+				v: V,
+				#[json(skip_if = "Option::is_none")]
+				w: Option<(u8, W)>
+			}
+		}), "T : :: json_builder :: IntoJSON , W : :: json_builder :: IntoJSON");
+		assert_eq!(bounds(parse_quote! {
+			struct A<T: Clone>(#[json(with = "write_t")] T) where T: Copy;
+		}), "T : Copy");
+		assert_eq!(bounds(parse_quote! {
+			struct A<'a, T>(&'a T, #[json(skip)] T);
+		}), "T : :: json_builder :: IntoJSON");
+	}
+}
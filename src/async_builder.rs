@@ -0,0 +1,258 @@
+use std::future::Future;
+use std::io;
+use std::vec::Vec;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::json_builder::{Error, IntoJSON, JSONBuilder, Result};
+
+// For types that have to await something while being written, e.g. rows
+// that are streamed from a database. Implementations may use async fn. The
+// returned future has to be Send so documents can be written from spawned
+// tasks.
+#[allow(clippy::wrong_self_convention)]
+pub trait AsyncIntoJSON {
+	fn into_json_async<W: AsyncWrite + Unpin + Send>(&self, builder: &mut AsyncJSONBuilder<W>) -> impl Future<Output = Result> + Send;
+}
+
+const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+
+// Drives a JSONBuilder that writes into memory and passes its output on to
+// the AsyncWrite whenever at least chunk_size bytes are pending. Values are
+// serialized synchronously, so a single large value is held in memory as a
+// whole. Stream large documents element by element instead.
+pub struct AsyncJSONBuilder<W> {
+	builder: JSONBuilder<Vec<u8>>,
+	writer: W,
+	chunk_size: usize
+}
+
+impl<W: AsyncWrite + Unpin> AsyncJSONBuilder<W> {
+	pub fn new(writer: W) -> AsyncJSONBuilder<W> {
+		AsyncJSONBuilder {
+			builder: JSONBuilder::new(Vec::new()),
+			writer,
+			chunk_size: DEFAULT_CHUNK_SIZE
+		}
+	}
+
+	pub fn new_pretty(writer: W, indent_size: usize, tab_indent: bool) -> AsyncJSONBuilder<W> {
+		AsyncJSONBuilder {
+			builder: JSONBuilder::new_pretty(Vec::new(), indent_size, tab_indent),
+			writer,
+			chunk_size: DEFAULT_CHUNK_SIZE
+		}
+	}
+
+	// The underlying builder, e.g. for its settings. What is written through
+	// it goes out with the next async call.
+	pub fn sync_builder(&mut self) -> &mut JSONBuilder<Vec<u8>> {
+		&mut self.builder
+	}
+
+	pub fn get_ref(&self) -> &W {
+		&self.writer
+	}
+
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.writer
+	}
+
+	// pending output that wasn't flushed is discarded
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+
+	pub fn set_chunk_size(&mut self, chunk_size: usize) {
+		self.chunk_size = chunk_size;
+	}
+
+	pub fn chunk_size(&self) -> usize {
+		self.chunk_size
+	}
+
+	// Cancel safe: written bytes are removed from the pending output right
+	// away, so if the future is dropped the next call continues after them.
+	async fn write_pending(&mut self, min_size: usize) -> Result {
+		// moves what the builder buffered into the pending output
+		self.builder.flush()?;
+		let pending = self.builder.get_mut();
+		if pending.is_empty() || pending.len() < min_size {
+			return Ok(());
+		}
+		while !pending.is_empty() {
+			match self.writer.write(pending).await {
+				Ok(0) => return Err(Error::IO(io::ErrorKind::WriteZero.into())),
+				Ok(size) => { pending.drain(..size); },
+				Err(err) => return Err(Error::IO(err))
+			}
+		}
+		Ok(())
+	}
+
+	pub async fn flush(&mut self) -> Result {
+		self.write_pending(0).await?;
+		match self.writer.flush().await {
+			Ok(()) => Ok(()),
+			Err(err) => Err(Error::IO(err))
+		}
+	}
+
+	pub async fn value<Value: IntoJSON>(&mut self, value: Value) -> Result {
+		self.builder.value(value)?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn value_async<Value: AsyncIntoJSON + ?Sized>(&mut self, value: &Value) -> Result where W: Send {
+		value.into_json_async(self).await
+	}
+
+	pub async fn null(&mut self) -> Result {
+		self.builder.null()?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn key(&mut self, key: &str) -> Result {
+		self.builder.key(key)?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn item<Value: IntoJSON>(&mut self, key: &str, value: Value) -> Result {
+		self.builder.item(key, value)?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn begin_array(&mut self) -> Result {
+		self.builder.begin_array()?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn end_array(&mut self) -> Result {
+		self.builder.end_array()?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn begin_object(&mut self) -> Result {
+		self.builder.begin_object()?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	pub async fn end_object(&mut self) -> Result {
+		self.builder.end_object()?;
+		self.write_pending(self.chunk_size).await
+	}
+
+	// checks that the document is complete and flushes it
+	pub async fn end(&mut self) -> Result {
+		self.builder.end()?;
+		self.flush().await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::pin::Pin;
+	use std::task::{Context, Poll, Waker};
+	use tokio::io::AsyncReadExt;
+
+	struct Rows(u32);
+
+	impl AsyncIntoJSON for Rows {
+		async fn into_json_async<W: AsyncWrite + Unpin + Send>(&self, builder: &mut AsyncJSONBuilder<W>) -> Result {
+			builder.begin_array().await?;
+			for id in 0..self.0 {
+				tokio::task::yield_now().await;
+				builder.value(id).await?;
+			}
+			builder.end_array().await
+		}
+	}
+
+	#[tokio::test]
+	async fn writes_through_duplex_streams() {
+		let (writer, mut reader) = tokio::io::duplex(4);
+		let read = tokio::spawn(async move {
+			let mut output = String::new();
+			reader.read_to_string(&mut output).await.unwrap();
+			output
+		});
+
+		let mut builder = AsyncJSONBuilder::new(writer);
+		builder.set_chunk_size(8);
+		builder.sync_builder().set_buffer_capacity(64);
+		builder.begin_object().await.unwrap();
+		builder.item("name", "rows").await.unwrap();
+		builder.key("rows").await.unwrap();
+		builder.value_async(&Rows(3)).await.unwrap();
+		builder.end_object().await.unwrap();
+		builder.end().await.unwrap();
+		drop(builder);
+
+		assert_eq!(read.await.unwrap(), "{\"name\":\"rows\",\"rows\":[0,1,2]}");
+	}
+
+	#[tokio::test]
+	async fn passes_on_chunks() {
+		let mut builder = AsyncJSONBuilder::new(Vec::new());
+		builder.set_chunk_size(4);
+		builder.sync_builder().set_buffer_capacity(64);
+		builder.begin_array().await.unwrap();
+		builder.value("abc").await.unwrap();
+		assert_eq!(builder.get_ref(), b"[\"abc\"");
+		builder.value(1).await.unwrap();
+		assert_eq!(builder.get_ref(), b"[\"abc\"");
+		builder.end_array().await.unwrap();
+		builder.end().await.unwrap();
+		assert_eq!(builder.into_inner(), b"[\"abc\",1]");
+	}
+
+	// accepts at most 2 bytes per write and every other write is pending
+	#[derive(Default)]
+	struct Throttled {
+		output: Vec<u8>,
+		blocked: bool
+	}
+
+	impl AsyncWrite for Throttled {
+		fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bytes: &[u8]) -> Poll<io::Result<usize>> {
+			if self.blocked {
+				self.blocked = false;
+				cx.waker().wake_by_ref();
+				return Poll::Pending;
+			}
+			self.blocked = true;
+			let size = bytes.len().min(2);
+			self.output.extend_from_slice(&bytes[..size]);
+			Poll::Ready(Ok(size))
+		}
+
+		fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+			Poll::Ready(Ok(()))
+		}
+	}
+
+	#[tokio::test]
+	async fn continues_after_cancelled_writes() {
+		let mut builder = AsyncJSONBuilder::new(Throttled::default());
+		builder.set_chunk_size(1);
+		{
+			let future = std::pin::pin!(builder.value("abcdef"));
+			assert!(future.poll(&mut Context::from_waker(Waker::noop())).is_pending());
+		}
+		assert_eq!(builder.get_ref().output, b"\"a");
+		builder.end().await.unwrap();
+		assert_eq!(builder.get_ref().output, b"\"abcdef\"");
+	}
+
+	#[test]
+	fn is_send() {
+		fn assert_send<T: Send>(_: T) {}
+		let mut builder = AsyncJSONBuilder::new(Vec::new());
+		assert_send(async move { builder.value_async(&Rows(1)).await });
+	}
+}
@@ -0,0 +1,251 @@
+use alloc::vec::Vec;
+
+use crate::json_builder::{Error, Result};
+use crate::sink::Sink;
+
+// Collects the many small writes of the builder and passes them on in
+// chunks of up to capacity bytes. A capacity of 0 disables buffering.
+// Unlike std::io::BufWriter the capacity can be changed at any time. Like
+// it, buffered output is written on drop, ignoring errors.
+pub(crate) struct BufferedWriter<W: Sink> {
+	// only None after into_inner()
+	inner: Option<W>,
+	buffer: Vec<u8>,
+	capacity: usize,
+	// output that is held back by the inline layout
+	captured: Vec<u8>,
+	capturing: bool
+}
+
+impl<W: Sink> BufferedWriter<W> {
+	pub(crate) fn new(inner: W) -> BufferedWriter<W> {
+		BufferedWriter {
+			inner: Some(inner),
+			buffer: Vec::new(),
+			capacity: 0,
+			captured: Vec::new(),
+			capturing: false
+		}
+	}
+
+	pub(crate) fn get_ref(&self) -> &W {
+		self.inner.as_ref().unwrap()
+	}
+
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		self.inner.as_mut().unwrap()
+	}
+
+	pub(crate) fn into_inner(mut self) -> core::result::Result<W, Error> {
+		self.flush_buffer()?;
+		Ok(self.inner.take().unwrap())
+	}
+
+	pub(crate) fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	// a buffer that holds more than the new capacity is written out on the next write
+	pub(crate) fn set_capacity(&mut self, capacity: usize) {
+		if capacity > self.buffer.capacity() {
+			self.buffer.reserve_exact(capacity - self.buffer.len());
+		}
+		self.capacity = capacity;
+	}
+
+	// Until end_capture() writes only go to the captured output. Returns
+	// where they start in it.
+	pub(crate) fn begin_capture(&mut self) -> usize {
+		self.capturing = true;
+		self.captured.len()
+	}
+
+	// returns where the captured writes end
+	pub(crate) fn end_capture(&mut self) -> usize {
+		self.capturing = false;
+		self.captured.len()
+	}
+
+	pub(crate) fn captured_len(&self) -> usize {
+		self.captured.len()
+	}
+
+	pub(crate) fn is_capturing(&self) -> bool {
+		self.capturing
+	}
+
+	pub(crate) fn captured(&self, start: usize, end: usize) -> &[u8] {
+		&self.captured[start..end]
+	}
+
+	pub(crate) fn write_captured(&mut self, start: usize, end: usize) -> Result {
+		let captured = core::mem::take(&mut self.captured);
+		let result = self.write_bytes(&captured[start..end]);
+		self.captured = captured;
+		result
+	}
+
+	pub(crate) fn clear_captured(&mut self) {
+		self.captured.clear();
+	}
+
+	// Splits off a writer to the same sink that continues with the buffered
+	// and captured output of this one, the slot takes it back. See
+	// JSONBuilder::with_dyn().
+	pub(crate) fn split_dyn(&mut self) -> (BufferedWriter<&mut dyn Sink>, WriterSlot<'_>) {
+		let sink = self.inner.as_mut().unwrap() as &mut dyn Sink;
+		let mut slot = WriterSlot {
+			buffer: &mut self.buffer,
+			capacity: &mut self.capacity,
+			captured: &mut self.captured,
+			capturing: &mut self.capturing
+		};
+		(slot.take(sink), slot)
+	}
+
+	// Like split_dyn(), but the split off writer writes to another sink.
+	pub(crate) fn split_with<S: Sink>(&mut self, sink: S) -> (BufferedWriter<S>, WriterSlot<'_>) {
+		let mut slot = WriterSlot {
+			buffer: &mut self.buffer,
+			capacity: &mut self.capacity,
+			captured: &mut self.captured,
+			capturing: &mut self.capturing
+		};
+		(slot.take(sink), slot)
+	}
+
+	fn flush_buffer(&mut self) -> Result {
+		if !self.buffer.is_empty() {
+			self.inner.as_mut().unwrap().write_bytes(&self.buffer)?;
+			self.buffer.clear();
+		}
+		Ok(())
+	}
+}
+
+// Everything of a BufferedWriter except its sink, see split_dyn().
+pub(crate) struct WriterSlot<'a> {
+	buffer: &'a mut Vec<u8>,
+	capacity: &'a mut usize,
+	captured: &'a mut Vec<u8>,
+	capturing: &'a mut bool
+}
+
+impl<'a> WriterSlot<'a> {
+	fn take<S: Sink>(&mut self, sink: S) -> BufferedWriter<S> {
+		BufferedWriter {
+			inner: Some(sink),
+			buffer: core::mem::take(self.buffer),
+			capacity: *self.capacity,
+			captured: core::mem::take(self.captured),
+			capturing: *self.capturing
+		}
+	}
+
+	pub(crate) fn restore<S: Sink>(&mut self, writer: &mut BufferedWriter<S>) {
+		*self.buffer = core::mem::take(&mut writer.buffer);
+		*self.capacity = writer.capacity;
+		*self.captured = core::mem::take(&mut writer.captured);
+		*self.capturing = writer.capturing;
+	}
+}
+
+impl<W: Sink> Sink for BufferedWriter<W> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		if self.capturing {
+			self.captured.extend_from_slice(bytes);
+			return Ok(());
+		}
+		if self.buffer.len() + bytes.len() > self.capacity {
+			self.flush_buffer()?;
+		}
+		if bytes.len() >= self.capacity {
+			self.get_mut().write_bytes(bytes)
+		} else {
+			self.buffer.extend_from_slice(bytes);
+			Ok(())
+		}
+	}
+
+	fn flush_sink(&mut self) -> Result {
+		self.flush_buffer()?;
+		self.get_mut().flush_sink()
+	}
+}
+
+impl<W: Sink> Drop for BufferedWriter<W> {
+	fn drop(&mut self) {
+		if self.inner.is_some() {
+			let _ = self.flush_buffer();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+	use crate::json_builder::{AutoFlush, JSONBuilder};
+
+	// records the writes that reach the sink
+	#[derive(Default)]
+	struct Writes(Vec<Vec<u8>>);
+
+	impl Sink for Writes {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+			self.0.push(bytes.to_vec());
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn writes_in_chunks() {
+		let mut writes = Writes::default();
+		{
+			let mut writer = BufferedWriter::new(&mut writes as &mut dyn Sink);
+			writer.set_capacity(8);
+			writer.write_bytes(b"abc").unwrap();
+			writer.write_bytes(b"def").unwrap();
+			writer.write_bytes(b"ghi").unwrap();
+			writer.write_bytes(b"0123456789").unwrap();
+			writer.write_bytes(b"x").unwrap();
+		}
+		assert_eq!(writes.0, vec![b"abcdef".to_vec(), b"ghi".to_vec(), b"0123456789".to_vec(), b"x".to_vec()]);
+	}
+
+	#[test]
+	fn flushes_on_drop() {
+		let mut output = Vec::new();
+		{
+			let mut builder = JSONBuilder::new(&mut output as &mut dyn Sink);
+			builder.set_buffer_capacity(1024);
+			builder.value(&[1, 2, 3][..]).unwrap();
+			builder.end().unwrap();
+		}
+		assert_eq!(output, b"[1,2,3]");
+
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.set_buffer_capacity(1024);
+		builder.value("done").unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"\"done\"");
+	}
+
+	#[test]
+	fn flushes_automatically() {
+		let mut writes = Writes::default();
+		{
+			let mut builder = JSONBuilder::new(&mut writes as &mut dyn Sink);
+			builder.set_buffer_capacity(1024);
+			builder.set_auto_flush(AutoFlush::RootElement);
+			builder.begin_array().unwrap();
+			builder.value(&[1, 2][..]).unwrap();
+			builder.value("a").unwrap();
+			builder.end_array().unwrap();
+			builder.reset();
+			builder.set_auto_flush(AutoFlush::TopLevelValue);
+			builder.value(&[3][..]).unwrap();
+			builder.value(4).unwrap_err();
+		}
+		assert_eq!(writes.0, vec![b"[[1,2]".to_vec(), b",\"a\"".to_vec(), b"]".to_vec(), b"[3]".to_vec()]);
+	}
+}
@@ -0,0 +1,155 @@
+use alloc::string::String;
+
+// ANSI escape sequences the builder writes before each kind of token. An
+// empty sequence leaves that kind uncolored.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ColorScheme {
+	pub key: String,
+	pub string: String,
+	pub number: String,
+	pub boolean: String,
+	pub null: String,
+	// brackets, commas and colons
+	pub punctuation: String,
+	// written after each colored token
+	pub reset: String
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum Style {
+	Key,
+	String,
+	Number,
+	Boolean,
+	Null,
+	Punctuation
+}
+
+impl ColorScheme {
+	// the default colors of jq
+	pub fn jq() -> ColorScheme {
+		ColorScheme {
+			key: "\x1b[34;1m".into(),
+			string: "\x1b[0;32m".into(),
+			number: "\x1b[0;39m".into(),
+			boolean: "\x1b[0;39m".into(),
+			null: "\x1b[0;90m".into(),
+			punctuation: "\x1b[1;39m".into(),
+			reset: "\x1b[0m".into()
+		}
+	}
+
+	// The scheme if stream is a terminal and the NO_COLOR environment
+	// variable isn't set, otherwise None. To check the writer of a builder
+	// use JSONBuilder::set_color_scheme_if_terminal() instead of passing
+	// another handle to the same stream.
+	#[cfg(feature = "std")]
+	pub fn if_terminal<T: std::io::IsTerminal + ?Sized>(self, stream: &T) -> Option<ColorScheme> {
+		let no_color = match std::env::var_os("NO_COLOR") {
+			Some(value) => !value.is_empty(),
+			None => false
+		};
+		if no_color || !stream.is_terminal() {
+			None
+		} else {
+			Some(self)
+		}
+	}
+
+	pub(crate) fn sequence(&self, style: Style) -> &str {
+		match style {
+			Style::Key => &self.key,
+			Style::String => &self.string,
+			Style::Number => &self.number,
+			Style::Boolean => &self.boolean,
+			Style::Null => &self.null,
+			Style::Punctuation => &self.punctuation
+		}
+	}
+}
+
+impl Default for ColorScheme {
+	fn default() -> ColorScheme {
+		ColorScheme::jq()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::json_builder::JSONBuilder;
+	use crate::pretty_config::PrettyConfig;
+
+	fn markers() -> ColorScheme {
+		ColorScheme {
+			key: "K".into(),
+			string: "S".into(),
+			number: "N".into(),
+			boolean: "B".into(),
+			null: "0".into(),
+			punctuation: "P".into(),
+			reset: "/".into()
+		}
+	}
+
+	fn colored(colors: ColorScheme, config: Option<PrettyConfig>) -> String {
+		let mut builder = JSONBuilder::new_fmt(String::new());
+		builder.set_pretty_config(config);
+		builder.set_color_scheme(Some(colors));
+		builder.begin_object().unwrap();
+		builder.item("a", &[1, 2][..]).unwrap();
+		builder.item("b", "x").unwrap();
+		builder.key("c").unwrap();
+		builder.null().unwrap();
+		builder.item("d", true).unwrap();
+		builder.end_object().unwrap();
+		builder.into_inner().unwrap().into_inner()
+	}
+
+	// removes the escape sequences of the colors
+	fn plain(colored: &str) -> String {
+		let mut plain = String::new();
+		let mut escape = false;
+		for c in colored.chars() {
+			if c == '\x1b' {
+				escape = true;
+			} else if !escape {
+				plain.push(c);
+			} else if c == 'm' {
+				escape = false;
+			}
+		}
+		plain
+	}
+
+	#[test]
+	fn colors_each_kind_of_token() {
+		assert_eq!(colored(markers(), None), "P{/K\"a\"/P:/P[/N1/P,/N2/P]/P,/K\"b\"/P:/S\"x\"/P,/K\"c\"/P:/0null/P,/K\"d\"/P:/Btrue/P}/");
+		let colors = ColorScheme { punctuation: String::new(), ..markers() };
+		assert_eq!(colored(colors, Some(PrettyConfig::single_line())), "{K\"a\"/: [N1/, N2/], K\"b\"/: S\"x\"/, K\"c\"/: 0null/, K\"d\"/: Btrue/}");
+	}
+
+	#[test]
+	fn ignores_colors_for_the_width() {
+		let output = colored(ColorScheme::jq(), Some(PrettyConfig { max_width: 45, ..PrettyConfig::spaces(2) }));
+		assert!(output.starts_with("\x1b[1;39m{\x1b[0m\x1b[34;1m\"a\"\x1b[0m"));
+		assert_eq!(plain(&output), "{\"a\": [1, 2], \"b\": \"x\", \"c\": null, \"d\": true}");
+
+		let output = colored(ColorScheme::jq(), Some(PrettyConfig { max_width: 44, ..PrettyConfig::spaces(2) }));
+		assert_eq!(plain(&output), "{\n  \"a\": [1, 2],\n  \"b\": \"x\",\n  \"c\": null,\n  \"d\": true\n}");
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn checks_the_writer_for_a_terminal() {
+		let path = std::env::temp_dir().join(format!("json_builder_colors_{}.json", std::process::id()));
+		let file = std::fs::File::create(&path).unwrap();
+		let mut builder = JSONBuilder::new(file);
+		builder.set_color_scheme_if_terminal(markers());
+		assert_eq!(builder.color_scheme(), None);
+		builder.value(true).unwrap();
+		drop(builder);
+		assert_eq!(std::fs::read(&path).unwrap(), b"true");
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,170 @@
+use core::ops::{Deref, DerefMut};
+
+use crate::json_builder::JSONBuilder;
+use crate::sink::Sink;
+
+// What to do when a guarded builder is dropped with open arrays or objects.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DropGuard {
+	// close them with finish() and flush the output, ignoring errors
+	Close,
+	// panic in debug builds, do nothing in release builds
+	Panic
+}
+
+// A builder that checks on drop whether its document was finished.
+// Dereferences to the JSONBuilder, so it's used just like one.
+pub struct GuardedJSONBuilder<W: Sink> {
+	builder: Option<JSONBuilder<W>>,
+	guard: DropGuard
+}
+
+impl<W: Sink> JSONBuilder<W> {
+	pub fn guard(self, guard: DropGuard) -> GuardedJSONBuilder<W> {
+		GuardedJSONBuilder {
+			builder: Some(self),
+			guard
+		}
+	}
+}
+
+impl<W: Sink> GuardedJSONBuilder<W> {
+	pub fn drop_guard(&self) -> DropGuard {
+		self.guard
+	}
+
+	// returns the builder without the guard
+	pub fn into_inner(mut self) -> JSONBuilder<W> {
+		self.builder.take().unwrap()
+	}
+}
+
+impl<W: Sink> Deref for GuardedJSONBuilder<W> {
+	type Target = JSONBuilder<W>;
+
+	fn deref(&self) -> &JSONBuilder<W> {
+		self.builder.as_ref().unwrap()
+	}
+}
+
+impl<W: Sink> DerefMut for GuardedJSONBuilder<W> {
+	fn deref_mut(&mut self) -> &mut JSONBuilder<W> {
+		self.builder.as_mut().unwrap()
+	}
+}
+
+impl<W: Sink> Drop for GuardedJSONBuilder<W> {
+	fn drop(&mut self) {
+		let builder = match self.builder {
+			Some(ref mut builder) => builder,
+			None => return
+		};
+
+		match self.guard {
+			DropGuard::Close => {
+				if builder.depth() > 0 {
+					let _ = builder.finish();
+				}
+				// finish() doesn't flush when closing fails
+				let _ = builder.flush();
+			},
+
+			DropGuard::Panic if builder.depth() == 0 => {},
+
+			DropGuard::Panic => {
+				#[cfg(feature = "std")]
+				{
+					if std::thread::panicking() {
+						return;
+					}
+				}
+				if cfg!(debug_assertions) {
+					panic!("JSONBuilder dropped with an unfinished document at {}", builder.path());
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+	use alloc::vec::Vec;
+	use crate::json_builder::Result;
+
+	#[derive(Default)]
+	struct Output {
+		bytes: Vec<u8>,
+		flushes: usize
+	}
+
+	impl Sink for Output {
+		fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+			self.bytes.extend_from_slice(bytes);
+			Ok(())
+		}
+
+		fn flush_sink(&mut self) -> Result {
+			self.flushes += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn closes_and_flushes_on_drop() {
+		let mut output = Output::default();
+		{
+			let mut builder = JSONBuilder::new(&mut output as &mut dyn Sink).guard(DropGuard::Close);
+			builder.set_buffer_capacity(1024);
+			builder.begin_object().unwrap();
+			builder.key("list").unwrap();
+			builder.begin_array().unwrap();
+			builder.value(1).unwrap();
+			builder.begin_object().unwrap();
+			builder.key("open").unwrap();
+		}
+		assert_eq!(output.bytes, b"{\"list\":[1,{\"open\":null}]}");
+		assert_eq!(output.flushes, 2);
+
+		let mut output = Output::default();
+		{
+			let mut builder = JSONBuilder::new(&mut output as &mut dyn Sink).guard(DropGuard::Close);
+			builder.set_buffer_capacity(1024);
+			builder.value(&vec![1, 2][..]).unwrap();
+		}
+		assert_eq!(output.bytes, b"[1,2]");
+		assert_eq!(output.flushes, 1);
+	}
+
+	#[test]
+	fn finishes_and_resets() {
+		let mut builder = JSONBuilder::new(Vec::new()).guard(DropGuard::Panic);
+		builder.begin_array().unwrap();
+		builder.begin_array().unwrap();
+		builder.finish().unwrap();
+		assert_eq!(builder.depth(), 0);
+		builder.reset();
+		builder.begin_object().unwrap();
+		builder.key("a").unwrap();
+		builder.reset();
+		builder.value(true).unwrap();
+		assert_eq!(builder.into_inner().into_inner().unwrap(), b"[[]]{\"a\":true");
+	}
+
+	#[test]
+	fn leaves_finished_documents_alone() {
+		let mut builder = JSONBuilder::new(Vec::new()).guard(DropGuard::Panic);
+		builder.value(1).unwrap();
+		assert_eq!(builder.drop_guard(), DropGuard::Panic);
+	}
+
+	#[cfg(debug_assertions)]
+	#[test]
+	#[should_panic(expected = "JSONBuilder dropped with an unfinished document at $[1]")]
+	fn panics_on_unfinished_documents() {
+		let mut builder = JSONBuilder::new(Vec::new()).guard(DropGuard::Panic);
+		builder.begin_array().unwrap();
+		builder.value(1).unwrap();
+	}
+}
@@ -0,0 +1,457 @@
+use std::io::Read;
+use std::vec::Vec;
+use std::convert::TryFrom;
+
+use crate::json_reader::{JSONReader, ReadResult, Event};
+
+pub trait FromJSON: Sized {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self>;
+
+	// The value of an object field that isn't there, None makes it an error.
+	fn missing() -> Option<Self> {
+		None
+	}
+
+	fn from_json_str(json: &str) -> ReadResult<Self> {
+		let mut reader = JSONReader::new(json.as_bytes());
+		let value = Self::from_json(&mut reader)?;
+		reader.end()?;
+		Ok(value)
+	}
+}
+
+macro_rules! impl_from_json_for_number {
+	($($t:ty),+) => {
+		$(impl FromJSON for $t {
+			fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+				let parsed = match reader.next_event()? {
+					Some(Event::Number(number)) => number.parse::<$t>(),
+					_ => return reader.type_error("a number")
+				};
+				match parsed {
+					Ok(value) => Ok(value),
+					Err(_) => reader.value_error(concat!("number not representable as ", stringify!($t)))
+				}
+			}
+		})*
+	}
+}
+
+impl_from_json_for_number!{
+	i8, i16, i32, i64, i128,
+	u8, u16, u32, u64, u128,
+	isize, usize
+}
+
+// Literals beyond the range of the type parse as infinity, which is rejected
+// like integers that don't fit.
+macro_rules! impl_from_json_for_float {
+	($($t:ty),+) => {
+		$(impl FromJSON for $t {
+			fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+				let parsed = match reader.next_event()? {
+					Some(Event::Number(number)) => number.parse::<$t>(),
+					_ => return reader.type_error("a number")
+				};
+				match parsed {
+					Ok(value) if value.is_finite() => Ok(value),
+					_ => reader.value_error(concat!("number not representable as ", stringify!($t)))
+				}
+			}
+		})*
+	}
+}
+
+impl_from_json_for_float!{
+	f32, f64
+}
+
+impl FromJSON for bool {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		match reader.next_event()? {
+			Some(Event::Bool(value)) => Ok(value),
+			_ => reader.type_error("a boolean")
+		}
+	}
+}
+
+impl FromJSON for String {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		match reader.next_event()? {
+			Some(Event::String(value)) => Ok(value.to_string()),
+			_ => reader.type_error("a string")
+		}
+	}
+}
+
+impl FromJSON for char {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		let c = match reader.next_event()? {
+			Some(Event::String(value)) => {
+				let mut chars = value.chars();
+				match (chars.next(), chars.next()) {
+					(Some(c), None) => Some(c),
+					_ => None
+				}
+			},
+			_ => return reader.type_error("a string")
+		};
+		match c {
+			Some(c) => Ok(c),
+			None => reader.value_error("string is not a single character")
+		}
+	}
+}
+
+impl<T: FromJSON> FromJSON for Option<T> {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		if let Some(Event::Null) = reader.peek_event()? {
+			reader.next_event()?;
+			return Ok(None);
+		}
+		T::from_json(reader).map(Some)
+	}
+
+	fn missing() -> Option<Self> {
+		Some(None)
+	}
+}
+
+impl<T: FromJSON> FromJSON for Box<T> {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		T::from_json(reader).map(Box::new)
+	}
+}
+
+macro_rules! impl_from_json_for_map {
+	($($t:ident),+) => {
+		$(
+			impl<T: FromJSON> FromJSON for std::collections::$t<String, T> {
+				fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+					let mut map = std::collections::$t::new();
+					reader.begin_object()?;
+					while let Some(key) = reader.next_key()? {
+						if map.contains_key(&key) {
+							return reader.duplicate_field();
+						}
+						let value = T::from_json(reader)?;
+						map.insert(key, value);
+					}
+					Ok(map)
+				}
+			}
+		)+
+	}
+}
+
+impl_from_json_for_map!{
+	BTreeMap,
+	HashMap
+}
+
+macro_rules! impl_from_json_for_iterable {
+	($($t:ty where $($bound:path),*);+) => {
+		$(
+			impl<T: FromJSON $(+ $bound)*> FromJSON for $t {
+				fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+					let mut items = <$t>::new();
+					reader.begin_array()?;
+					while reader.next_element()? {
+						items.extend(Some(T::from_json(reader)?));
+					}
+					Ok(items)
+				}
+			}
+		)+
+	}
+}
+
+impl_from_json_for_iterable!{
+	Vec<T> where;
+	std::collections::BTreeSet<T> where Ord;
+	std::collections::LinkedList<T> where;
+	std::collections::VecDeque<T> where
+}
+
+macro_rules! impl_from_json_for_array {
+	($($n:expr),+) => {
+		$(
+			impl<T: FromJSON> FromJSON for [T; $n] {
+				fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+					let items = Vec::<T>::from_json(reader)?;
+					match <[T; $n]>::try_from(items) {
+						Ok(array) => Ok(array),
+						Err(_) => reader.value_error(concat!("array length is not ", stringify!($n)))
+					}
+				}
+			}
+		)+
+	}
+}
+
+impl_from_json_for_array!{
+	0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+	17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_json_internal_key {
+	($key:ident ($id:ident)) => {
+		$key == stringify!($id)
+	};
+
+	($key:ident ([$id:expr])) => {
+		$key == $id
+	};
+
+	($key:ident ($id:expr)) => {
+		$key == $id
+	};
+}
+
+// Builds an if-else chain over the keys of the field list. Plain fields are read
+// into their locals, computed entries are only written and thus skipped when reading.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_json_internal {
+	($r:ident $key:ident () ()) => {
+		return $r.unknown_field();
+	};
+
+	($r:ident $key:ident () (,)) => {
+		return $r.unknown_field();
+	};
+
+	($r:ident $key:ident ($($id:tt)*) (: $ex:expr , $($more:tt)*)) => {
+		if $crate::impl_from_json_internal_key!($key ($($id)*)) {
+			$r.skip_value()?;
+		} else {
+			$crate::impl_from_json_internal!($r $key () ($($more)*));
+		}
+	};
+
+	($r:ident $key:ident ($($id:tt)*) (: $ex:expr)) => {
+		if $crate::impl_from_json_internal_key!($key ($($id)*)) {
+			$r.skip_value()?;
+		} else {
+			return $r.unknown_field();
+		}
+	};
+
+	($r:ident $key:ident ($id:ident) ()) => {
+		$crate::impl_from_json_internal!($r $key ($id) (,));
+	};
+
+	($r:ident $key:ident ($id:ident) (, $($more:tt)*)) => {
+		if $key == stringify!($id) {
+			if $id.is_some() {
+				return $r.duplicate_field();
+			}
+			$id = ::std::option::Option::Some($crate::FromJSON::from_json($r)?);
+		} else {
+			$crate::impl_from_json_internal!($r $key () ($($more)*));
+		}
+	};
+
+	($r:ident $key:ident ($($id:tt)*) (($($more:tt)*))) => {
+		$crate::impl_from_json_internal!($r $key ($($id)*) ($($more)*));
+	};
+
+	($r:ident $key:ident ($($id:tt)*) ($tt:tt $($more:tt)*)) => {
+		$crate::impl_from_json_internal!($r $key ($($id)* $tt) ($($more)*));
+	};
+}
+
+// declares a local for every plain field
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_json_internal_locals {
+	(() ()) => {};
+
+	(($id:ident) ($(,)?)) => {
+		let mut $id = ::std::option::Option::None;
+	};
+
+	(($id:ident) (, $($more:tt)*)) => {
+		$crate::impl_from_json_internal_locals!(($id) ());
+		$crate::impl_from_json_internal_locals!(() ($($more)*));
+	};
+
+	(($($id:tt)*) (: $ex:expr , $($more:tt)*)) => {
+		$crate::impl_from_json_internal_locals!(() ($($more)*));
+	};
+
+	(($($id:tt)*) (: $ex:expr)) => {};
+
+	(($($id:tt)*) (($($more:tt)*))) => {
+		$crate::impl_from_json_internal_locals!(($($id)*) ($($more)*));
+	};
+
+	(($($id:tt)*) ($tt:tt $($more:tt)*)) => {
+		$crate::impl_from_json_internal_locals!(($($id)* $tt) ($($more)*));
+	};
+}
+
+// Collects the plain fields and builds the struct from their locals. A field
+// that wasn't read takes FromJSON::missing(), computed entries are never required.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_json_internal_build {
+	($r:ident [$($base:tt)*] [$($done:ident)*] () ($(,)?)) => {
+		::std::result::Result::Ok(Self {
+			$($done: match $done.or_else($crate::FromJSON::missing) {
+				::std::option::Option::Some(value) => value,
+				::std::option::Option::None => return $r.missing_field(stringify!($done))
+			},)*
+			$($base)*
+		})
+	};
+
+	($r:ident $base:tt [$($done:ident)*] ($id:ident) ($(,)?)) => {
+		$crate::impl_from_json_internal_build!($r $base [$($done)* $id] () ())
+	};
+
+	($r:ident $base:tt [$($done:ident)*] ($id:ident) (, $($more:tt)*)) => {
+		$crate::impl_from_json_internal_build!($r $base [$($done)* $id] () ($($more)*))
+	};
+
+	($r:ident $base:tt $done:tt ($($id:tt)*) (: $ex:expr , $($more:tt)*)) => {
+		$crate::impl_from_json_internal_build!($r $base $done () ($($more)*))
+	};
+
+	($r:ident $base:tt $done:tt ($($id:tt)*) (: $ex:expr)) => {
+		$crate::impl_from_json_internal_build!($r $base $done () ())
+	};
+
+	($r:ident $base:tt $done:tt ($($id:tt)*) (($($more:tt)*))) => {
+		$crate::impl_from_json_internal_build!($r $base $done ($($id)*) ($($more)*))
+	};
+
+	($r:ident $base:tt $done:tt ($($id:tt)*) ($tt:tt $($more:tt)*)) => {
+		$crate::impl_from_json_internal_build!($r $base $done ($($id)* $tt) ($($more)*))
+	};
+}
+
+// Accepts the same field list as impl_into_json!. Every field of the struct has
+// to be listed, unless the type is followed by [default], which takes the fields
+// that aren't listed from its Default implementation.
+#[macro_export]
+macro_rules! impl_from_json {
+	($t:ty [default]) => {
+		$crate::impl_from_json!($t [default], );
+	};
+
+	($t:ty [default], $($def:tt)*) => {
+		$crate::impl_from_json!(@impl $t, [..::std::default::Default::default()], $($def)*);
+	};
+
+	($t:ty) => {
+		$crate::impl_from_json!($t, );
+	};
+
+	($t:ty, $($def:tt)*) => {
+		$crate::impl_from_json!(@impl $t, [], $($def)*);
+	};
+
+	(@impl $t:ty, $base:tt, $($def:tt)*) => {
+		impl $crate::FromJSON for $t {
+			// the locals are named after the fields
+			#[allow(clippy::disallowed_names)]
+			fn from_json<R: ::std::io::Read>(reader: &mut $crate::JSONReader<R>) -> $crate::ReadResult<Self> {
+				$crate::impl_from_json_internal_locals!(() ($($def)*));
+				reader.begin_object()?;
+				while let Some(key) = reader.next_key()? {
+					let key = key.as_str();
+					$crate::impl_from_json_internal!(reader key () ($($def)*));
+				}
+				$crate::impl_from_json_internal_build!(reader $base [] () ($($def)*))
+			}
+		}
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::{BTreeMap, BTreeSet, VecDeque};
+	use crate::json_reader::ReadError;
+
+	#[derive(PartialEq, Debug)]
+	struct Point {
+		x: i32,
+		y: i32,
+		label: Option<String>
+	}
+
+	impl_from_json! {
+		Point,
+		x, y, label,
+		norm: |this| this.x * this.x + this.y * this.y
+	}
+
+	#[derive(Default, PartialEq, Debug)]
+	struct Partial {
+		name: String,
+		cache: Vec<u8>
+	}
+
+	impl_from_json! {
+		Partial [default],
+		name
+	}
+
+	fn error<T: FromJSON + std::fmt::Debug>(json: &str) -> String {
+		match T::from_json_str(json) {
+			Err(ReadError::Type(expected, path)) => format!("type {} {}", expected, path),
+			Err(ReadError::Value(message, path)) => format!("value {} {}", message, path),
+			Err(ReadError::MissingField(path)) => format!("missing {}", path),
+			Err(ReadError::UnknownField(path)) => format!("unknown {}", path),
+			Err(ReadError::DuplicateField(path)) => format!("duplicate {}", path),
+			other => panic!("unexpected result: {:?}", other)
+		}
+	}
+
+	#[test]
+	fn reads_values() {
+		assert_eq!(i8::from_json_str("-128").unwrap(), -128);
+		assert_eq!(u128::from_json_str("340282366920938463463374607431768211455").unwrap(), u128::MAX);
+		assert_eq!(f64::from_json_str("2.5e-3").unwrap(), 0.0025);
+		assert_eq!(char::from_json_str("\"ä\"").unwrap(), 'ä');
+		assert_eq!(Option::<bool>::from_json_str("null").unwrap(), None);
+		assert_eq!(Box::<String>::from_json_str("\"a\\tb\"").unwrap(), Box::new("a\tb".to_string()));
+		assert_eq!(VecDeque::<u8>::from_json_str("[1, 2]").unwrap(), vec![1, 2]);
+		assert_eq!(BTreeSet::<u8>::from_json_str("[2, 1, 2]").unwrap().into_iter().collect::<Vec<_>>(), vec![1, 2]);
+		assert_eq!(<[i32; 3]>::from_json_str("[1, 2, 3]").unwrap(), [1, 2, 3]);
+
+		let map = BTreeMap::<String, Vec<bool>>::from_json_str("{\"a\": [true], \"b\": []}").unwrap();
+		assert_eq!(map.len(), 2);
+		assert_eq!(map["a"], vec![true]);
+
+		assert_eq!(Point::from_json_str("{\"y\": 2, \"norm\": {\"ignored\": []}, \"x\": 1, \"label\": \"p\"}").unwrap(), Point {
+			x: 1,
+			y: 2,
+			label: Some("p".to_string())
+		});
+		assert_eq!(Point::from_json_str("{\"x\": 1, \"y\": 2}").unwrap(), Point { x: 1, y: 2, label: None });
+		assert_eq!(Partial::from_json_str("{\"name\": \"a\"}").unwrap(), Partial { name: "a".to_string(), cache: vec![] });
+	}
+
+	#[test]
+	fn reports_errors_with_path() {
+		assert_eq!(error::<u8>("256"), "value number not representable as u8 $");
+		assert_eq!(error::<f32>("1e39"), "value number not representable as f32 $");
+		assert_eq!(error::<Vec<f64>>("[1, -1e400]"), "value number not representable as f64 $[1]");
+		assert_eq!(error::<Vec<u8>>("[1, -1]"), "value number not representable as u8 $[1]");
+		assert_eq!(error::<Vec<bool>>("[true, 1]"), "type a boolean $[1]");
+		assert_eq!(error::<char>("\"ab\""), "value string is not a single character $");
+		assert_eq!(error::<[u8; 2]>("[1]"), "value array length is not 2 $");
+		assert_eq!(error::<BTreeMap<String, u8>>("{\"a\": 1, \"a\": 2}"), "duplicate $.a");
+		assert_eq!(error::<Vec<Point>>("[{\"x\": 1, \"label\": null}]"), "missing $[0].y");
+		assert_eq!(error::<Partial>("{\"cache\": []}"), "unknown $.cache");
+		assert_eq!(error::<Partial>("{}"), "missing $.name");
+		assert_eq!(error::<Point>("{\"x\": 1, \"z\": 2}"), "unknown $.z");
+		assert_eq!(error::<Point>("{\"x\": 1, \"x\": 2}"), "duplicate $.x");
+		assert_eq!(error::<Point>("[]"), "type an object $");
+	}
+}
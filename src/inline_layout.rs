@@ -0,0 +1,619 @@
+use alloc::vec::Vec;
+
+use crate::buffered_writer::BufferedWriter;
+use crate::color_scheme::ColorScheme;
+use crate::json_builder::{Error, Result};
+use crate::pretty_config::{EmptyStyle, PrettyConfig, SPACES, write_line_break};
+use crate::sink::Sink;
+
+// Limit of an object held back for align_keys, in tokens plus the bytes of
+// their output.
+const MAX_HELD: usize = 64 * 1024;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Token {
+	Open(u8),
+	Close(u8),
+	// start and end in the captured output of the writer
+	Key(usize, usize),
+	Scalar(usize, usize)
+}
+
+// Implements PrettyConfig::max_width and align_keys. Everything the builder
+// writes arrives here as a token. An opened container is held back until it's
+// closed, then it's written on one line, or until it doesn't fit anymore, then
+// it's broken and its tokens are laid out again one by one, starting with the
+// next container among them. So only about max_width columns are held back,
+// plus the longest single value.
+//
+// With align_keys objects are instead held back as a whole, as all their keys
+// are needed before the first value can be written. Up to MAX_HELD, larger
+// objects are written without aligned keys.
+pub(crate) struct InlineLayout {
+	pending: Vec<Token>,
+	// the held back object, the number of its containers still open and
+	// where its output starts in the captured output
+	held: Vec<Token>,
+	held_open: usize,
+	held_start: usize,
+	// inline width of pending and the number of its containers still open
+	width: usize,
+	open: usize,
+	start_column: usize,
+	column: usize,
+	// number of open containers that are broken
+	depth: usize,
+	// last written token
+	prev: Option<Token>,
+	capture_start: usize,
+	// see Dialect::JSON5
+	trailing_commas: bool
+}
+
+fn needs_comma(prev: Option<Token>, token: Token) -> bool {
+	match prev {
+		Some(Token::Scalar(..)) | Some(Token::Close(_)) => !matches!(token, Token::Close(_)),
+		_ => false
+	}
+}
+
+// in characters, not bytes, and without the escape sequences of colors
+fn text_width(bytes: &[u8]) -> usize {
+	let mut width = 0;
+	let mut escape = false;
+	for &byte in bytes {
+		if escape {
+			// the final byte of "ESC [ parameters final"
+			escape = !(0x40..=0x7E).contains(&byte) || byte == b'[';
+		} else if byte == 0x1B {
+			escape = true;
+		} else if byte & 0xC0 != 0x80 {
+			width += 1;
+		}
+	}
+	width
+}
+
+fn comma_width(config: &PrettyConfig) -> usize {
+	config.space_before_comma as usize + 1 + config.space_after_comma as usize
+}
+
+fn colon_width(config: &PrettyConfig) -> usize {
+	config.space_before_colon as usize + 1 + config.space_after_colon as usize
+}
+
+impl InlineLayout {
+	pub(crate) fn new(trailing_commas: bool) -> InlineLayout {
+		InlineLayout {
+			pending: Vec::new(),
+			held: Vec::new(),
+			held_open: 0,
+			held_start: 0,
+			width: 0,
+			open: 0,
+			start_column: 0,
+			column: 0,
+			depth: 0,
+			prev: None,
+			capture_start: 0,
+			trailing_commas
+		}
+	}
+
+	pub(crate) fn set_trailing_commas(&mut self, trailing_commas: bool) {
+		self.trailing_commas = trailing_commas;
+	}
+
+	// captures the following output up to the next key(), value() or open()
+	pub(crate) fn begin_capture<W: Sink>(&mut self, writer: &mut BufferedWriter<W>) {
+		self.capture_start = writer.begin_capture();
+	}
+
+	pub(crate) fn key<W: Sink>(&mut self, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		let end = writer.end_capture();
+		self.token(Token::Key(self.capture_start, end), config, colors, writer)
+	}
+
+	pub(crate) fn value<W: Sink>(&mut self, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		let end = writer.end_capture();
+		self.token(Token::Scalar(self.capture_start, end), config, colors, writer)
+	}
+
+	pub(crate) fn open<W: Sink>(&mut self, bracket: u8, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		writer.end_capture();
+		self.token(Token::Open(bracket), config, colors, writer)
+	}
+
+	pub(crate) fn close<W: Sink>(&mut self, bracket: u8, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		self.token(Token::Close(bracket), config, colors, writer)
+	}
+
+	// after a complete top-level value, which is never held back
+	pub(crate) fn end_document(&mut self, line_break: bool) {
+		self.prev = None;
+		if line_break {
+			self.column = 0;
+		}
+	}
+
+	// drops what is held back
+	pub(crate) fn reset<W: Sink>(&mut self, writer: &mut BufferedWriter<W>) {
+		writer.end_capture();
+		writer.clear_captured();
+		self.pending.clear();
+		self.held.clear();
+		self.depth = 0;
+		self.prev = None;
+	}
+
+	fn token<W: Sink>(&mut self, token: Token, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		self.layout(token, config, colors, writer)?;
+		if self.pending.is_empty() && self.held.is_empty() {
+			writer.clear_captured();
+		}
+		Ok(())
+	}
+
+	fn layout<W: Sink>(&mut self, token: Token, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		if !self.held.is_empty() {
+			return self.hold_object(token, config, colors, writer);
+		}
+		if !self.pending.is_empty() {
+			return self.hold(token, config, colors, writer);
+		}
+
+		match token {
+			Token::Open(b'{') if config.align_keys => {
+				self.separate(token, config, colors, writer)?;
+				self.held_open = 0;
+				self.held_start = writer.captured_len();
+				return self.hold_object(token, config, colors, writer);
+			},
+
+			Token::Open(_) => {
+				self.separate(token, config, colors, writer)?;
+				self.start_column = self.column;
+				self.width = 0;
+				self.open = 0;
+				return self.hold(token, config, colors, writer);
+			},
+
+			Token::Close(bracket) => {
+				self.depth -= 1;
+				if let Some(Token::Open(_)) = self.prev {
+					match config.empty_style {
+						EmptyStyle::Compact => {},
+						EmptyStyle::Space => self.write(b" ", writer)?,
+						EmptyStyle::Expanded => self.line_break(config, writer)?
+					}
+				} else {
+					if self.trailing_commas {
+						self.punctuation(b",", colors, writer)?;
+					}
+					self.line_break(config, writer)?;
+				}
+				self.punctuation(&[bracket], colors, writer)?;
+			},
+
+			Token::Key(start, end) => {
+				self.separate(token, config, colors, writer)?;
+				self.write_captured(start, end, writer)?;
+				self.colon(config, colors, writer)?;
+			},
+
+			Token::Scalar(start, end) => {
+				self.separate(token, config, colors, writer)?;
+				self.write_captured(start, end, writer)?;
+			}
+		}
+
+		self.prev = Some(token);
+		Ok(())
+	}
+
+	// inline width of token when it follows last
+	fn token_width<W: Sink>(last: Option<Token>, token: Token, config: &PrettyConfig, writer: &BufferedWriter<W>) -> usize {
+		let comma = if needs_comma(last, token) { comma_width(config) } else { 0 };
+		comma + match token {
+			Token::Open(_) => 1,
+			Token::Close(_) => match last {
+				Some(Token::Open(_)) if config.empty_style == EmptyStyle::Space => 2,
+				// an expanded empty container never fits on one line
+				Some(Token::Open(_)) if config.empty_style == EmptyStyle::Expanded => config.max_width + 1,
+				_ => 1
+			},
+			Token::Key(start, end) => text_width(writer.captured(start, end)) + colon_width(config),
+			Token::Scalar(start, end) => text_width(writer.captured(start, end))
+		}
+	}
+
+	// adds a token to the held back container
+	fn hold<W: Sink>(&mut self, token: Token, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		self.width += InlineLayout::token_width(self.pending.last().cloned(), token, config, writer);
+		match token {
+			Token::Open(_) => self.open += 1,
+			Token::Close(_) => self.open -= 1,
+			_ => {}
+		}
+		self.pending.push(token);
+
+		// the open containers need at least a closing bracket each
+		if self.start_column + self.width + self.open > config.max_width {
+			self.break_pending(config, colors, writer)
+		} else if self.open == 0 {
+			let mut pending = core::mem::take(&mut self.pending);
+			self.write_inline(&pending, config, colors, writer)?;
+			pending.clear();
+			self.pending = pending;
+			Ok(())
+		} else {
+			Ok(())
+		}
+	}
+
+	// adds a token to the held back object, which is written once it's closed
+	fn hold_object<W: Sink>(&mut self, token: Token, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		match token {
+			Token::Open(_) => self.held_open += 1,
+			Token::Close(_) => self.held_open -= 1,
+			_ => {}
+		}
+		self.held.push(token);
+
+		if self.held_open == 0 {
+			let mut held = core::mem::take(&mut self.held);
+			self.write_value(&held, 0, config, colors, writer)?;
+			held.clear();
+			self.held = held;
+		} else if self.held.len() + writer.captured_len() - self.held_start > MAX_HELD {
+			self.release_held(config, colors, writer)?;
+		}
+		Ok(())
+	}
+
+	// Gives up aligning the keys of the held back object. It's laid out like
+	// any other container instead, objects in it are aligned on their own.
+	fn release_held<W: Sink>(&mut self, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		let held = core::mem::take(&mut self.held);
+		// separate() was called when the object was opened
+		self.start_column = self.column;
+		self.width = 0;
+		self.open = 0;
+		self.hold(held[0], config, colors, writer)?;
+		for &token in &held[1..] {
+			self.layout(token, config, colors, writer)?;
+		}
+		Ok(())
+	}
+
+	// Lays out the complete value that starts at tokens[index] and returns
+	// the index after it.
+	fn write_value<W: Sink>(&mut self, tokens: &[Token], index: usize, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> core::result::Result<usize, Error> {
+		let open = tokens[index];
+		if let Token::Scalar(start, end) = open {
+			self.write_captured(start, end, writer)?;
+			self.prev = Some(open);
+			return Ok(index + 1);
+		}
+
+		let mut level = 0;
+		let mut close = index;
+		let mut width = 0;
+		let mut last = None;
+		for (i, &token) in tokens.iter().enumerate().skip(index) {
+			width += InlineLayout::token_width(last, token, config, writer);
+			last = Some(token);
+			match token {
+				Token::Open(_) => level += 1,
+				Token::Close(_) => level -= 1,
+				_ => {}
+			}
+			if level == 0 {
+				close = i;
+				break;
+			}
+		}
+
+		if config.max_width > 0 && self.column + width <= config.max_width {
+			self.write_inline(&tokens[index..=close], config, colors, writer)?;
+			return Ok(close + 1);
+		}
+
+		let key_column = match open {
+			Token::Open(b'{') if config.align_keys => key_column(tokens, index, close, config, writer),
+			_ => 0
+		};
+
+		if let Token::Open(bracket) = open {
+			self.punctuation(&[bracket], colors, writer)?;
+		}
+		self.depth += 1;
+		self.prev = Some(open);
+
+		let mut i = index + 1;
+		while i < close {
+			self.separate(tokens[i], config, colors, writer)?;
+			if let Token::Key(start, end) = tokens[i] {
+				self.write_captured(start, end, writer)?;
+				self.colon(config, colors, writer)?;
+				let mut padding = key_column.saturating_sub(text_width(writer.captured(start, end)));
+				while padding > 0 {
+					let count = padding.min(SPACES.len());
+					self.write(&SPACES[..count], writer)?;
+					padding -= count;
+				}
+				self.prev = Some(tokens[i]);
+				i += 1;
+			}
+			i = self.write_value(tokens, i, config, colors, writer)?;
+		}
+
+		self.layout(tokens[close], config, colors, writer)?;
+		Ok(close + 1)
+	}
+
+	fn write_inline<W: Sink>(&mut self, tokens: &[Token], config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		let mut prev = None;
+		for &token in tokens {
+			if needs_comma(prev, token) {
+				self.comma(true, config, colors, writer)?;
+			}
+
+			match token {
+				Token::Open(bracket) => self.punctuation(&[bracket], colors, writer)?,
+
+				Token::Close(bracket) => {
+					if let Some(Token::Open(_)) = prev {
+						if config.empty_style == EmptyStyle::Space {
+							self.write(b" ", writer)?;
+						}
+					}
+					self.punctuation(&[bracket], colors, writer)?;
+				},
+
+				Token::Key(start, end) => {
+					self.write_captured(start, end, writer)?;
+					self.colon(config, colors, writer)?;
+				},
+
+				Token::Scalar(start, end) => self.write_captured(start, end, writer)?
+			}
+
+			prev = Some(token);
+		}
+
+		self.prev = prev;
+		Ok(())
+	}
+
+	fn break_pending<W: Sink>(&mut self, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		let pending = core::mem::take(&mut self.pending);
+		if let Some(&Token::Open(bracket)) = pending.first() {
+			self.punctuation(&[bracket], colors, writer)?;
+			self.depth += 1;
+			self.prev = Some(pending[0]);
+		}
+
+		for &token in &pending[1..] {
+			self.layout(token, config, colors, writer)?;
+		}
+
+		Ok(())
+	}
+
+	// comma and line break before a token in a broken container
+	fn separate<W: Sink>(&mut self, token: Token, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		if let Some(Token::Key(..)) = self.prev {
+			return Ok(());
+		}
+		if needs_comma(self.prev, token) {
+			self.comma(false, config, colors, writer)?;
+		}
+		if self.depth > 0 {
+			self.line_break(config, writer)?;
+		}
+		Ok(())
+	}
+
+	fn write<W: Sink>(&mut self, bytes: &[u8], writer: &mut BufferedWriter<W>) -> Result {
+		writer.write_bytes(bytes)?;
+		self.column += text_width(bytes);
+		Ok(())
+	}
+
+	fn punctuation<W: Sink>(&mut self, bytes: &[u8], colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		match colors {
+			Some(colors) if !colors.punctuation.is_empty() => {
+				writer.write_bytes(colors.punctuation.as_bytes())?;
+				self.write(bytes, writer)?;
+				writer.write_bytes(colors.reset.as_bytes())
+			},
+			_ => self.write(bytes, writer)
+		}
+	}
+
+	fn write_captured<W: Sink>(&mut self, start: usize, end: usize, writer: &mut BufferedWriter<W>) -> Result {
+		writer.write_captured(start, end)?;
+		self.column += text_width(writer.captured(start, end));
+		Ok(())
+	}
+
+	fn line_break<W: Sink>(&mut self, config: &PrettyConfig, writer: &mut BufferedWriter<W>) -> Result {
+		write_line_break(writer, config, self.depth)?;
+		self.column = self.depth * text_width(config.indent.as_bytes());
+		Ok(())
+	}
+
+	fn comma<W: Sink>(&mut self, inline: bool, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		if config.space_before_comma {
+			self.write(b" ", writer)?;
+		}
+		self.punctuation(b",", colors, writer)?;
+		if inline && config.space_after_comma {
+			self.write(b" ", writer)?;
+		}
+		Ok(())
+	}
+
+	fn colon<W: Sink>(&mut self, config: &PrettyConfig, colors: Option<&ColorScheme>, writer: &mut BufferedWriter<W>) -> Result {
+		if config.space_before_colon {
+			self.write(b" ", writer)?;
+		}
+		self.punctuation(b":", colors, writer)?;
+		if config.space_after_colon {
+			self.write(b" ", writer)?;
+		}
+		Ok(())
+	}
+}
+
+// Column the values of the object from tokens[open] to tokens[close] are
+// aligned to, relative to the start of their keys. Keys that would need more
+// than max_key_padding to line up with the longest key are left out.
+fn key_column<W: Sink>(tokens: &[Token], open: usize, close: usize, config: &PrettyConfig, writer: &BufferedWriter<W>) -> usize {
+	let mut widths = Vec::new();
+	let mut level = 0;
+	for &token in &tokens[open + 1..close] {
+		match token {
+			Token::Open(_) => level += 1,
+			Token::Close(_) => level -= 1,
+			Token::Key(start, end) if level == 0 => widths.push(text_width(writer.captured(start, end))),
+			_ => {}
+		}
+	}
+
+	let shortest = match widths.iter().min() {
+		Some(&shortest) => shortest,
+		None => return 0
+	};
+	widths.into_iter()
+		.filter(|&width| width <= shortest + config.max_key_padding)
+		.max()
+		.unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::format;
+	use alloc::string::String;
+	use crate::json_builder::{IntoJSON, JSONBuilder};
+
+	fn inline<T: IntoJSON>(value: T, config: PrettyConfig) -> String {
+		let mut builder = JSONBuilder::new_fmt_with_pretty_config(String::new(), config);
+		builder.value(value).unwrap();
+		builder.into_inner().unwrap().into_inner()
+	}
+
+	fn width(max_width: usize) -> PrettyConfig {
+		PrettyConfig { max_width, ..PrettyConfig::spaces(2) }
+	}
+
+	#[test]
+	fn keeps_containers_that_fit_on_one_line() {
+		let value: &[&[u32]] = &[&[1, 2, 3], &[], &[100000, 200000, 300000]];
+		assert_eq!(inline(value, width(80)), "[[1, 2, 3], [], [100000, 200000, 300000]]");
+		assert_eq!(inline(value, width(30)), "[\n  [1, 2, 3],\n  [],\n  [100000, 200000, 300000]\n]");
+		assert_eq!(inline(value, width(20)), "[\n  [1, 2, 3],\n  [],\n  [\n    100000,\n    200000,\n    300000\n  ]\n]");
+		assert_eq!(inline(value, width(0)), inline(value, PrettyConfig::spaces(2)));
+	}
+
+	#[test]
+	fn counts_characters_not_bytes() {
+		assert_eq!(inline(&["äöü", "€"][..], width(12)), "[\"äöü\", \"€\"]");
+		assert_eq!(inline(&["äöü", "€"][..], width(11)), "[\n  \"äöü\",\n  \"€\"\n]");
+	}
+
+	#[test]
+	fn breaks_around_expanded_empty_containers() {
+		let value: &[&[u32]] = &[&[1], &[]];
+		let config = PrettyConfig { empty_style: EmptyStyle::Expanded, ..width(80) };
+		assert_eq!(inline(value, config.clone()), "[\n  [1],\n  [\n  ]\n]");
+		assert_eq!(inline(&[0u32; 0][..], config.clone()), "[\n]");
+		let config = PrettyConfig { empty_style: EmptyStyle::Space, ..width(80) };
+		assert_eq!(inline(value, config), "[[1], [ ]]");
+	}
+
+	#[test]
+	fn lays_out_dyn_values() {
+		let value: &[&dyn IntoJSON] = &[&1u8, &"two", &&[3u8, 4][..]];
+		assert_eq!(inline(value, width(80)), "[1, \"two\", [3, 4]]");
+		assert_eq!(inline(value, width(12)), "[\n  1,\n  \"two\",\n  [3, 4]\n]");
+	}
+
+	fn aligned(max_width: usize, max_key_padding: usize) -> String {
+		let mut builder = JSONBuilder::new_fmt_with_pretty_config(String::new(), PrettyConfig {
+			align_keys: true,
+			max_width,
+			max_key_padding,
+			empty_style: EmptyStyle::Expanded,
+			..PrettyConfig::spaces(2)
+		});
+		builder.begin_object().unwrap();
+		builder.item("id", 1).unwrap();
+		builder.item("name", "x").unwrap();
+		builder.key("a rather long key").unwrap();
+		builder.begin_object().unwrap();
+		builder.item("b", true).unwrap();
+		builder.item("ccc", &[1, 2][..]).unwrap();
+		builder.end_object().unwrap();
+		builder.key("empty").unwrap();
+		builder.begin_object().unwrap();
+		builder.end_object().unwrap();
+		builder.end_object().unwrap();
+		builder.into_inner().unwrap().into_inner()
+	}
+
+	#[test]
+	fn aligns_keys() {
+		assert_eq!(aligned(0, 16), concat!(
+			"{\n",
+			"  \"id\":                1,\n",
+			"  \"name\":              \"x\",\n",
+			"  \"a rather long key\": {\n",
+			"    \"b\":   true,\n",
+			"    \"ccc\": [\n",
+			"      1,\n",
+			"      2\n",
+			"    ]\n",
+			"  },\n",
+			"  \"empty\":             {\n",
+			"  }\n",
+			"}"));
+	}
+
+	#[test]
+	fn caps_key_padding() {
+		assert_eq!(aligned(50, 4), concat!(
+			"{\n",
+			"  \"id\":    1,\n",
+			"  \"name\":  \"x\",\n",
+			"  \"a rather long key\": {\"b\": true, \"ccc\": [1, 2]},\n",
+			"  \"empty\": {\n",
+			"  }\n",
+			"}"));
+	}
+
+	#[test]
+	fn caps_held_back_objects() {
+		let config = PrettyConfig { align_keys: true, ..PrettyConfig::spaces(2) };
+		let mut output = Vec::new();
+		{
+			let mut builder = JSONBuilder::new_with_pretty_config(&mut output, config);
+			builder.begin_object().unwrap();
+			for i in 0..MAX_HELD / 4 {
+				builder.item(format!("k{}", i).as_str(), i).unwrap();
+			}
+			// the start of the object isn't held back anymore
+			assert!(builder.get_ref().starts_with(b"{\n  \"k0\": 0,\n  \"k1\": 1,\n"));
+			builder.key("nested").unwrap();
+			builder.begin_object().unwrap();
+			builder.item("a", 1).unwrap();
+			builder.item("bbb", 2).unwrap();
+			builder.end_object().unwrap();
+			builder.end_object().unwrap();
+		}
+		let output = String::from_utf8(output).unwrap();
+		assert!(output.ends_with("\n  \"nested\": {\n    \"a\":   1,\n    \"bbb\": 2\n  }\n}"));
+	}
+}
@@ -1,9 +1,17 @@
-extern crate std;
-
-use std::io::Write;
-use std::vec::Vec;
-
-// TODO: custom derive
+use core::fmt;
+use alloc::vec;
+use alloc::vec::Vec;
+use alloc::string::{String, ToString};
+use alloc::boxed::Box;
+
+use crate::json_value::{Discard, Map, Value, ValueTree};
+use crate::json_path::{JSONPath, is_identifier};
+use crate::number_format::{FloatFormat, HEX_DIGITS, Hex, write_float, write_hex_integer, write_integer};
+use crate::pretty_config::{EmptyStyle, LineEnding, PrettyConfig, write_line_break};
+use crate::buffered_writer::{BufferedWriter, WriterSlot};
+use crate::color_scheme::{ColorScheme, Style};
+use crate::inline_layout::InlineLayout;
+use crate::sink::{SharedOutput, Sink};
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum State {
@@ -16,150 +24,731 @@ pub enum State {
 	ObjectValue
 }
 
+// Describes what is expected next in a state.
+impl fmt::Display for State {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			State::Begin => "a top-level value",
+			State::End => "the end of the document",
+			State::ArrayFirstElement | State::ArrayElement => "an array element",
+			State::ObjectFirstKey | State::ObjectKey => "an object key",
+			State::ObjectValue => "an object value"
+		})
+	}
+}
+
+const EXPECT_VALUE: &[State] = &[State::Begin, State::ArrayElement, State::ArrayFirstElement, State::ObjectValue];
+const EXPECT_KEY: &[State] = &[State::ObjectFirstKey, State::ObjectKey];
+const EXPECT_END_ARRAY: &[State] = &[State::ArrayElement, State::ArrayFirstElement];
+const EXPECT_END_OBJECT: &[State] = &[State::ObjectKey, State::ObjectFirstKey];
+const EXPECT_END: &[State] = &[State::End];
+
+// What to write for NaN and infinite floats, which plain JSON can't represent.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum NonFinitePolicy {
+	// fail with Error::NonFinite
+	#[default]
+	Error,
+	Null,
+	// the strings "NaN", "Infinity" and "-Infinity"
+	String,
+	// the bare JSON5 tokens NaN, Infinity and -Infinity
+	JSON5
+}
+
+// The syntax that is written. JSON5 leaves keys that are identifiers
+// unquoted, writes Hex integers in hex and NaN and infinite floats as NaN,
+// Infinity and -Infinity unless another NonFinitePolicy than Error is set.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum Dialect {
+	#[default]
+	JSON,
+	JSON5 {
+		// 'string' instead of "string"
+		single_quotes: bool,
+		// a comma after the last element where a line break follows it
+		trailing_commas: bool
+	}
+}
+
+impl Dialect {
+	pub fn json5() -> Dialect {
+		Dialect::JSON5 { single_quotes: false, trailing_commas: true }
+	}
+}
+
+// When to flush the output without an explicit call of flush().
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum AutoFlush {
+	#[default]
+	Never,
+	// after each complete top-level value
+	TopLevelValue,
+	// additionally after each element of the root array or object
+	RootElement
+}
+
+#[derive(Debug)]
 pub enum Error {
+	#[cfg(feature = "std")]
 	IO(std::io::Error),
-	State(State, Vec<State>)
+	// the builder method that was called, the state it was called in, the
+	// states in which it would have been valid and where it was called
+	State {
+		call: &'static str,
+		state: State,
+		expected: &'static [State],
+		path: JSONPath
+	},
+	// the violated policy, the value and where it was written
+	NonFinite {
+		policy: NonFinitePolicy,
+		value: f64,
+		state: State,
+		path: JSONPath
+	},
+	// a fmt::Write sink failed
+	Fmt(fmt::Error),
+	// a fixed size sink has no room left
+	BufferFull
 }
 
-pub struct JSONBuilder<'a> {
+// Writes JSON to W, which can be an owned sink (a File, a Vec<u8>, ...)
+// or a borrowed one (&mut File, &mut Vec<u8>, ...). A JSONBuilder without
+// a type argument owns a boxed sink of any type.
+pub struct JSONBuilder<W: Sink = Box<dyn Sink>> {
 	stack: Vec<State>,
-	writer: &'a mut Write,
-	indent_size: usize,
-	tab_indent: bool
+	path: JSONPath,
+	writer: BufferedWriter<W>,
+	// None for compact output
+	pretty: Option<PrettyConfig>,
+	// only with a max_width or align_keys, see set_pretty_config()
+	layout: Option<InlineLayout>,
+	colors: Option<ColorScheme>,
+	dialect: Dialect,
+	escape_mode: EscapeMode,
+	non_finite_policy: NonFinitePolicy,
+	float_format: FloatFormat,
+	auto_flush: AutoFlush,
+	// only while collecting a Value, see IntoJSON::to_value()
+	values: Option<ValueTree>
 }
 
-pub type Result = std::result::Result<(), Error>;
+// Type erased builder, e.g. for passing builders through trait objects.
+pub type DynJSONBuilder<'a> = JSONBuilder<&'a mut dyn Sink>;
+
+pub type Result = core::result::Result<(), Error>;
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			#[cfg(feature = "std")]
+			Error::IO(ref err) => write!(f, "I/O error: {}", err),
+			Error::State { call, state, ref path, .. } =>
+				write!(f, "called {}() while {} was expected at {}", call, state, path),
+			Error::NonFinite { policy, value, state, ref path } =>
+				write!(f, "cannot write {} as {} at {}, non-finite numbers are not allowed by NonFinitePolicy::{:?}", value, state, path, policy),
+			Error::Fmt(_) => f.write_str("formatting error"),
+			Error::BufferFull => f.write_str("output buffer is full")
+		}
+	}
+}
+
+impl core::error::Error for Error {
+	fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+		match *self {
+			#[cfg(feature = "std")]
+			Error::IO(ref err) => Some(err),
+			Error::Fmt(ref err) => Some(err),
+			_ => None
+		}
+	}
+}
 
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+	fn from(err: std::io::Error) -> Error {
+		Error::IO(err)
+	}
+}
+
+// into_json() gets the builder of the default type, so it can be called
+// through &dyn IntoJSON. Builders with other sinks call write_json(), which
+// implementations should provide as well: the default collects the output
+// of into_json() in memory first, see JSONBuilder::with_boxed(). The
+// implementations of this crate, impl_into_json!() and the derive write
+// directly and just forward into_json() to write_json():
+//
+//     fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+//         self.write_json(builder)
+//     }
+#[allow(clippy::wrong_self_convention)]
 pub trait IntoJSON {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result;
 
-	fn to_json(&self) -> std::result::Result<String, Error> {
-		let mut data = Vec::<u8>::new();
-		{
-			let mut builder = JSONBuilder::new(&mut data);
-			self.into_json(&mut builder)?;
-			builder.end()?;
-		}
-		Ok(String::from_utf8(data).unwrap())
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result where Self: Sized {
+		builder.with_boxed(|builder| self.into_json(builder))
 	}
 
-	fn to_pretty_json(&self, indent_size: usize, tab_indent: bool) -> std::result::Result<String, Error> {
-		let mut data = Vec::<u8>::new();
-		{
-			let mut builder = JSONBuilder::new_pretty(&mut data, indent_size, tab_indent);
-			self.into_json(&mut builder)?;
-			builder.end()?;
+	fn to_json(&self) -> core::result::Result<String, Error> {
+		into_string(self, None)
+	}
+
+	fn to_pretty_json(&self, indent_size: usize, tab_indent: bool) -> core::result::Result<String, Error> {
+		into_string(self, PrettyConfig::from_indent(indent_size, tab_indent))
+	}
+
+	fn to_pretty_json_with_config(&self, config: PrettyConfig) -> core::result::Result<String, Error> {
+		into_string(self, Some(config))
+	}
+
+	// Builds the Value directly from the builder calls, so numbers keep
+	// their kind and nothing is formatted.
+	fn to_value(&self) -> core::result::Result<Value, Error> {
+		let mut builder: JSONBuilder = JSONBuilder::new(Box::new(Discard));
+		builder.values = Some(ValueTree::new());
+		self.into_json(&mut builder)?;
+		builder.end()?;
+		match builder.values.as_mut().and_then(ValueTree::take) {
+			Some(value) => Ok(value),
+			None => Ok(Value::Null)
 		}
-		Ok(String::from_utf8(data).unwrap())
 	}
 }
 
+fn into_string<T: IntoJSON + ?Sized>(value: &T, config: Option<PrettyConfig>) -> core::result::Result<String, Error> {
+	let output = SharedOutput::default();
+	let mut builder: JSONBuilder = JSONBuilder::new(Box::new(output.clone()));
+	builder.set_pretty_config(config);
+	value.into_json(&mut builder)?;
+	builder.end()?;
+	builder.into_inner()?;
+	// only fails if an implementation wrote invalid UTF-8 through get_mut()
+	String::from_utf8(output.take()).map_err(|_| Error::Fmt(fmt::Error))
+}
+
 macro_rules! write_bytes {
 	($builder:expr, $bytes:expr) => {
-		match $builder.writer.write_all($bytes) {
-			Err(err) => return Err(Error::IO(err)),
+		match $builder.writer.write_bytes($bytes) {
+			Err(err) => return Err(err),
 			_ => {}
 		}
 	};
 }
 
-pub fn escape_json(s: &str, writer: &mut Write) -> std::io::Result<()> {
-	let mut prev = 0usize;
-	for (i, c) in s.char_indices() {
-		match c {
-			'\\' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\\\")?;
-				prev = i + 1;
-			},
+// Each mode escapes everything the previous one does.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum EscapeMode {
+	// only what RFC 8259 requires: '"', '\\' and control characters
+	#[default]
+	Minimal,
+	// additionally '<', '>', '&', '\'' and '/', so the output can be embedded in HTML
+	HtmlSafe,
+	// additionally U+2028 and U+2029, which aren't allowed in JavaScript string literals
+	JavaScriptSafe,
+	// additionally every other non-ASCII character
+	AsciiOnly
+}
 
-			'"' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\\"")?;
-				prev = i + 1;
-			},
+// Escape tables indexed by byte value: 0 means the byte is written as is,
+// b'u' means \u00XX and any other ASCII char is the char of a short escape.
+// NON_ASCII marks lead bytes of chars that are written as \uXXXX and LINE_SEP
+// marks the lead byte of U+2028 and U+2029. Runs of all other bytes are copied
+// as they are, which keeps UTF-8 intact.
+const NON_ASCII: u8 = 0xFF;
+const LINE_SEP: u8 = 0xFE;
+
+const fn escape_table(mode: EscapeMode, single_quotes: bool) -> [u8; 256] {
+	let mut table = [0u8; 256];
+	let mut i = 0;
+	while i < 0x20 {
+		table[i] = b'u';
+		i += 1;
+	}
+	table[0x08] = b'b';
+	table[0x09] = b't';
+	table[0x0A] = b'n';
+	table[0x0C] = b'f';
+	table[0x0D] = b'r';
+	if single_quotes {
+		table[b'\'' as usize] = b'\'';
+	} else {
+		table[b'"' as usize] = b'"';
+	}
+	table[b'\\' as usize] = b'\\';
 
-			'\n' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\n")?;
-				prev = i + 1;
-			},
+	match mode {
+		EscapeMode::Minimal => return table,
+		EscapeMode::HtmlSafe | EscapeMode::JavaScriptSafe | EscapeMode::AsciiOnly => {}
+	}
+	table[b'<' as usize] = b'u';
+	table[b'>' as usize] = b'u';
+	table[b'&' as usize] = b'u';
+	table[b'\'' as usize] = b'u';
+	table[b'/' as usize] = b'/';
+
+	match mode {
+		EscapeMode::JavaScriptSafe => {
+			table[0xE2] = LINE_SEP;
+		},
+		EscapeMode::AsciiOnly => {
+			// continuation bytes are skipped together with their lead byte
+			let mut i = 0xC0;
+			while i < 0x100 {
+				table[i] = NON_ASCII;
+				i += 1;
+			}
+		},
+		_ => {}
+	}
+	table
+}
 
-			'\r' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\r")?;
-				prev = i + 1;
-			},
+static ESCAPE_MINIMAL: [u8; 256] = escape_table(EscapeMode::Minimal, false);
+static ESCAPE_HTML_SAFE: [u8; 256] = escape_table(EscapeMode::HtmlSafe, false);
+static ESCAPE_JAVASCRIPT_SAFE: [u8; 256] = escape_table(EscapeMode::JavaScriptSafe, false);
+static ESCAPE_ASCII_ONLY: [u8; 256] = escape_table(EscapeMode::AsciiOnly, false);
+
+// for JSON5 single quoted strings
+static ESCAPE_MINIMAL_SINGLE: [u8; 256] = escape_table(EscapeMode::Minimal, true);
+static ESCAPE_HTML_SAFE_SINGLE: [u8; 256] = escape_table(EscapeMode::HtmlSafe, true);
+static ESCAPE_JAVASCRIPT_SAFE_SINGLE: [u8; 256] = escape_table(EscapeMode::JavaScriptSafe, true);
+static ESCAPE_ASCII_ONLY_SINGLE: [u8; 256] = escape_table(EscapeMode::AsciiOnly, true);
+
+fn write_unicode_escape<W: Sink + ?Sized>(unit: u16, writer: &mut W) -> Result {
+	writer.write_bytes(&[
+		b'\\', b'u',
+		HEX_DIGITS[(unit >> 12) as usize],
+		HEX_DIGITS[((unit >> 8) & 0xF) as usize],
+		HEX_DIGITS[((unit >> 4) & 0xF) as usize],
+		HEX_DIGITS[(unit & 0xF) as usize]
+	])
+}
 
-			'<' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\u003c")?;
-				prev = i + 1;
-			},
+// escapes with EscapeMode::Minimal, see escape_json_with_mode()
+pub fn escape_json<W: Sink + ?Sized>(s: &str, writer: &mut W) -> Result {
+	escape_json_with_mode(s, EscapeMode::default(), writer)
+}
 
-			'>' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\u003e")?;
-				prev = i + 1;
-			},
+pub fn escape_json_with_mode<W: Sink + ?Sized>(s: &str, mode: EscapeMode, writer: &mut W) -> Result {
+	escape_string(s, mode, false, writer)
+}
 
-			'\0' => {
-				writer.write_all(s[prev..i].as_bytes())?;
-				writer.write_all(b"\\u0000")?;
-				prev = i + 1;
-			},
-			
+fn escape_string<W: Sink + ?Sized>(s: &str, mode: EscapeMode, single_quotes: bool, writer: &mut W) -> Result {
+	let table = match (mode, single_quotes) {
+		(EscapeMode::Minimal, false)        => &ESCAPE_MINIMAL,
+		(EscapeMode::HtmlSafe, false)       => &ESCAPE_HTML_SAFE,
+		(EscapeMode::JavaScriptSafe, false) => &ESCAPE_JAVASCRIPT_SAFE,
+		(EscapeMode::AsciiOnly, false)      => &ESCAPE_ASCII_ONLY,
+		(EscapeMode::Minimal, true)         => &ESCAPE_MINIMAL_SINGLE,
+		(EscapeMode::HtmlSafe, true)        => &ESCAPE_HTML_SAFE_SINGLE,
+		(EscapeMode::JavaScriptSafe, true)  => &ESCAPE_JAVASCRIPT_SAFE_SINGLE,
+		(EscapeMode::AsciiOnly, true)       => &ESCAPE_ASCII_ONLY_SINGLE
+	};
+	let bytes = s.as_bytes();
+	let mut start = 0usize;
+	let mut i = 0usize;
+	while i < bytes.len() {
+		let byte = bytes[i];
+		let escape = table[byte as usize];
+		if escape == 0 || (escape == LINE_SEP && !(bytes[i + 1] == 0x80 && (bytes[i + 2] == 0xA8 || bytes[i + 2] == 0xA9))) {
+			i += 1;
+			continue;
+		}
 
-			_ => {}
+		if start < i {
+			writer.write_bytes(&bytes[start..i])?;
+		}
+
+		match escape {
+			NON_ASCII | LINE_SEP => {
+				let c = s[i..].chars().next().unwrap();
+				let mut units = [0u16; 2];
+				for &unit in c.encode_utf16(&mut units).iter() {
+					write_unicode_escape(unit, writer)?;
+				}
+				i += c.len_utf8();
+			},
+			b'u' => {
+				write_unicode_escape(byte as u16, writer)?;
+				i += 1;
+			},
+			_ => {
+				writer.write_bytes(&[b'\\', escape])?;
+				i += 1;
+			}
 		}
+
+		start = i;
+	}
+
+	if start < bytes.len() {
+		writer.write_bytes(&bytes[start..])?;
 	}
-	writer.write_all(s[prev..].as_bytes())
+
+	Ok(())
 }
 
 macro_rules! write_string {
 	( $builder:expr, $str:expr ) => {		
-		write_bytes!($builder, b"\"");
-		match escape_json($str, $builder.writer) {
+		let single_quotes = matches!($builder.dialect, Dialect::JSON5 { single_quotes: true, .. });
+		let quote: &[u8] = if single_quotes { b"'" } else { b"\"" };
+		write_bytes!($builder, quote);
+		match escape_string($str, $builder.escape_mode, single_quotes, &mut $builder.writer) {
 			Ok(()) => {},
-			Err(err) => return Err(Error::IO(err)),
+			Err(err) => return Err(err),
+		}
+		write_bytes!($builder, quote);
+	}
+}
+
+// in JSON5 keys that are identifiers aren't quoted
+macro_rules! write_key {
+	( $builder:expr, $key:expr ) => {
+		if let Dialect::JSON5 { .. } = $builder.dialect {
+			if is_identifier($key) {
+				write_bytes!($builder, $key.as_bytes());
+			} else {
+				write_string!($builder, $key);
+			}
+		} else {
+			write_string!($builder, $key);
 		}
-		write_bytes!($builder, b"\"");
 	}
 }
 
-// optimization to write up to 512 tabs/spaces at once (instead of byte-for-byte)
-const TABS:[u8; 512] = [9u8; 512];
-const SPACES:[u8; 512] = [32u8; 512];
+// Everything of a builder except its writer, see split_state().
+struct StateSlot<'a> {
+	stack: &'a mut Vec<State>,
+	path: &'a mut JSONPath,
+	pretty: &'a mut Option<PrettyConfig>,
+	layout: &'a mut Option<InlineLayout>,
+	colors: &'a mut Option<ColorScheme>,
+	dialect: &'a mut Dialect,
+	escape_mode: &'a mut EscapeMode,
+	non_finite_policy: &'a mut NonFinitePolicy,
+	float_format: &'a mut FloatFormat,
+	auto_flush: &'a mut AutoFlush,
+	values: &'a mut Option<ValueTree>
+}
 
-impl<'a> JSONBuilder<'a> {
-	pub fn new(writer: &mut Write) -> JSONBuilder {
+impl<'a> StateSlot<'a> {
+	fn take<S: Sink>(&mut self, writer: BufferedWriter<S>) -> JSONBuilder<S> {
 		JSONBuilder {
-			stack: vec![ State::Begin ],
-			writer: writer,
-			indent_size: 0,
-			tab_indent: true
+			stack: core::mem::take(self.stack),
+			path: core::mem::take(self.path),
+			writer,
+			pretty: self.pretty.take(),
+			layout: self.layout.take(),
+			colors: self.colors.take(),
+			dialect: *self.dialect,
+			escape_mode: *self.escape_mode,
+			non_finite_policy: *self.non_finite_policy,
+			float_format: *self.float_format,
+			auto_flush: *self.auto_flush,
+			values: self.values.take()
 		}
 	}
 
-	pub fn new_pretty(writer: &mut Write, indent_size: usize, tab_indent: bool) -> JSONBuilder {
+	// destructured, so a new field can't be forgotten here
+	fn restore<S: Sink>(&mut self, builder: &mut JSONBuilder<S>) {
+		let JSONBuilder {
+			stack, path, writer: _, pretty, layout, colors, dialect, escape_mode,
+			non_finite_policy, float_format, auto_flush, values
+		} = builder;
+		*self.stack = core::mem::take(stack);
+		*self.path = core::mem::take(path);
+		*self.pretty = pretty.take();
+		*self.layout = layout.take();
+		*self.colors = colors.take();
+		*self.dialect = *dialect;
+		*self.escape_mode = *escape_mode;
+		*self.non_finite_policy = *non_finite_policy;
+		*self.float_format = *float_format;
+		*self.auto_flush = *auto_flush;
+		*self.values = values.take();
+	}
+}
+
+// Puts the state of a builder back when the builder of with_dyn() or
+// with_boxed() is done with it.
+struct DynGuard<'a, S: Sink> {
+	builder: JSONBuilder<S>,
+	writer: WriterSlot<'a>,
+	state: StateSlot<'a>
+}
+
+impl<'a, S: Sink> DynGuard<'a, S> {
+	fn new(writer: BufferedWriter<S>, slot: WriterSlot<'a>, mut state: StateSlot<'a>) -> DynGuard<'a, S> {
+		DynGuard {
+			builder: state.take(writer),
+			writer: slot,
+			state
+		}
+	}
+}
+
+impl<'a, S: Sink> Drop for DynGuard<'a, S> {
+	fn drop(&mut self) {
+		self.writer.restore(&mut self.builder.writer);
+		self.state.restore(&mut self.builder);
+	}
+}
+
+impl<W: Sink> JSONBuilder<W> {
+	pub fn new(writer: W) -> JSONBuilder<W> {
 		JSONBuilder {
 			stack: vec![ State::Begin ],
-			writer: writer,
-			indent_size: indent_size,
-			tab_indent: tab_indent
+			path: JSONPath::new(),
+			writer: BufferedWriter::new(writer),
+			pretty: None,
+			layout: None,
+			colors: None,
+			dialect: Dialect::default(),
+			escape_mode: EscapeMode::default(),
+			non_finite_policy: NonFinitePolicy::default(),
+			float_format: FloatFormat::default(),
+			auto_flush: AutoFlush::default(),
+			values: None
+		}
+	}
+
+	pub fn new_pretty(writer: W, indent_size: usize, tab_indent: bool) -> JSONBuilder<W> {
+		let mut builder = JSONBuilder::new(writer);
+		builder.set_pretty_config(PrettyConfig::from_indent(indent_size, tab_indent));
+		builder
+	}
+
+	pub fn new_with_pretty_config(writer: W, config: PrettyConfig) -> JSONBuilder<W> {
+		let mut builder = JSONBuilder::new(writer);
+		builder.set_pretty_config(Some(config));
+		builder
+	}
+
+	pub fn get_ref(&self) -> &W {
+		self.writer.get_ref()
+	}
+
+	// writing to the returned writer bypasses the output buffer
+	pub fn get_mut(&mut self) -> &mut W {
+		self.writer.get_mut()
+	}
+
+	// Flushes the output buffer and returns the writer, no matter whether
+	// the document is complete.
+	pub fn into_inner(self) -> core::result::Result<W, Error> {
+		self.writer.into_inner()
+	}
+
+	// Writes the buffered output and flushes the writer.
+	pub fn flush(&mut self) -> Result {
+		self.writer.flush_sink()
+	}
+
+	// Number of bytes collected before they are passed on to the writer,
+	// 0 (the default) writes everything through immediately. Buffered output
+	// is also written when the builder is dropped, but errors are lost then.
+	pub fn set_buffer_capacity(&mut self, capacity: usize) {
+		self.writer.set_capacity(capacity);
+	}
+
+	pub fn buffer_capacity(&self) -> usize {
+		self.writer.capacity()
+	}
+
+	pub fn set_auto_flush(&mut self, auto_flush: AutoFlush) {
+		self.auto_flush = auto_flush;
+	}
+
+	pub fn auto_flush(&self) -> AutoFlush {
+		self.auto_flush
+	}
+
+	// Runs f with a type erased builder that continues where this one is.
+	// The state is moved back even if f panics.
+	pub fn with_dyn<F>(&mut self, f: F) -> Result where F: FnOnce(&mut DynJSONBuilder) -> Result {
+		let (writer, state) = self.split_state();
+		let (writer, slot) = writer.split_dyn();
+		let mut guard = DynGuard::new(writer, slot, state);
+		f(&mut guard.builder)
+	}
+
+	// Like with_dyn(), but with a builder of the default type, as taken by
+	// IntoJSON::into_json(). That one can't borrow the writer of this
+	// builder, so its output is collected in memory and written to this
+	// builder's writer when f returns, even if f fails.
+	pub fn with_boxed<F>(&mut self, f: F) -> Result where F: FnOnce(&mut JSONBuilder) -> Result {
+		let output = SharedOutput::default();
+		let result = {
+			let (writer, state) = self.split_state();
+			let (writer, slot) = writer.split_with(Box::new(output.clone()) as Box<dyn Sink>);
+			let mut guard = DynGuard::new(writer, slot, state);
+			f(&mut guard.builder)
+		};
+		let output = output.take();
+		if output.is_empty() {
+			return result;
 		}
+		let written = self.writer.get_mut().write_bytes(&output);
+		result.and(written)
+	}
+
+	// destructured, so a new field can't be forgotten here
+	fn split_state(&mut self) -> (&mut BufferedWriter<W>, StateSlot<'_>) {
+		let JSONBuilder {
+			stack, path, writer, pretty, layout, colors, dialect, escape_mode,
+			non_finite_policy, float_format, auto_flush, values
+		} = self;
+		(writer, StateSlot {
+			stack, path, pretty, layout, colors, dialect, escape_mode,
+			non_finite_policy, float_format, auto_flush, values
+		})
+	}
+
+	// None switches to compact output. Set it before writing, output that
+	// is held back for the previous config is dropped.
+	pub fn set_pretty_config(&mut self, config: Option<PrettyConfig>) {
+		if let Some(ref mut layout) = self.layout {
+			layout.reset(&mut self.writer);
+		}
+		self.layout = match config {
+			Some(ref config) if (config.max_width > 0 || config.align_keys) && config.line_ending != LineEnding::None =>
+				Some(InlineLayout::new(self.trailing_commas())),
+			_ => None
+		};
+		self.pretty = config;
+	}
+
+	pub fn pretty_config(&self) -> Option<&PrettyConfig> {
+		self.pretty.as_ref()
+	}
+
+	// None (the default) writes no escape sequences, see
+	// set_color_scheme_if_terminal()
+	pub fn set_color_scheme(&mut self, colors: Option<ColorScheme>) {
+		self.colors = colors;
+	}
+
+	pub fn color_scheme(&self) -> Option<&ColorScheme> {
+		self.colors.as_ref()
 	}
 
-	fn before_value(&mut self) -> Result {
+	pub fn set_dialect(&mut self, dialect: Dialect) {
+		self.dialect = dialect;
+		let trailing_commas = self.trailing_commas();
+		if let Some(ref mut layout) = self.layout {
+			layout.set_trailing_commas(trailing_commas);
+		}
+	}
+
+	pub fn dialect(&self) -> Dialect {
+		self.dialect
+	}
+
+	fn trailing_commas(&self) -> bool {
+		matches!(self.dialect, Dialect::JSON5 { trailing_commas: true, .. })
+	}
+
+	// applies to keys and string values
+	pub fn set_escape_mode(&mut self, escape_mode: EscapeMode) {
+		self.escape_mode = escape_mode;
+	}
+
+	pub fn escape_mode(&self) -> EscapeMode {
+		self.escape_mode
+	}
+
+	pub fn set_non_finite_policy(&mut self, non_finite_policy: NonFinitePolicy) {
+		self.non_finite_policy = non_finite_policy;
+	}
+
+	pub fn non_finite_policy(&self) -> NonFinitePolicy {
+		self.non_finite_policy
+	}
+
+	pub fn set_float_format(&mut self, float_format: FloatFormat) {
+		self.float_format = float_format;
+	}
+
+	pub fn float_format(&self) -> FloatFormat {
+		self.float_format
+	}
+
+	// number of open arrays and objects
+	pub fn depth(&self) -> usize {
+		self.stack.len() - 1
+	}
+
+	// Location of the value that is written next. In objects it's the last
+	// key while the next key is expected.
+	pub fn path(&self) -> &JSONPath {
+		&self.path
+	}
+
+	pub fn state(&self) -> State {
+		*self.stack.last().unwrap()
+	}
+
+	// whether value() may be called now
+	pub fn expects_value(&self) -> bool {
+		EXPECT_VALUE.contains(&self.state())
+	}
+
+	fn state_error(&self, call: &'static str, expected: &'static [State]) -> Error {
+		Error::State { call, state: self.state(), expected, path: self.path.clone() }
+	}
+
+	fn non_finite(&mut self, value: f64) -> Result {
+		let token = if value.is_nan() {
+			"NaN"
+		} else if value > 0.0 {
+			"Infinity"
+		} else {
+			"-Infinity"
+		};
+
+		let policy = match self.dialect {
+			Dialect::JSON5 { .. } if self.non_finite_policy == NonFinitePolicy::Error => NonFinitePolicy::JSON5,
+			_ => self.non_finite_policy
+		};
+
+		match policy {
+			NonFinitePolicy::Error =>
+				Err(Error::NonFinite { policy: self.non_finite_policy, value, state: self.state(), path: self.path.clone() }),
+
+			NonFinitePolicy::Null => self.null(),
+
+			NonFinitePolicy::String => self.value(token),
+
+			NonFinitePolicy::JSON5 if self.values.is_some() =>
+				self.collect_value("value", Value::from(value)),
+
+			NonFinitePolicy::JSON5 => {
+				self.before_value("value")?;
+				self.begin_color(Style::Number)?;
+				write_bytes!(self, token.as_bytes());
+				self.end_color(Style::Number)?;
+				self.after_value()?;
+				Ok(())
+			}
+		}
+	}
+
+	fn before_value(&mut self, call: &'static str) -> Result {
 		let current = *self.stack.last().unwrap();
 		match current {
 			State::ObjectFirstKey | State::ObjectKey | State::End =>
-				return Err(Error::State(current, vec![
-					State::Begin, State::ArrayElement, State::ArrayFirstElement, State::ObjectValue
-				])),
+				return Err(self.state_error(call, EXPECT_VALUE)),
+
+			// the layout writes the separators once it knows the value
+			_ if self.layout.is_some() => {
+				if let Some(ref mut layout) = self.layout {
+					layout.begin_capture(&mut self.writer);
+				}
+			},
 
 			State::ArrayElement => {
-				write_bytes!(self, b",");
+				self.comma()?;
 				self.indent()?;
 			},
 
@@ -173,31 +762,135 @@ impl<'a> JSONBuilder<'a> {
 		Ok(())
 	}
 
+	// Takes the place of writing a scalar while collecting a Value.
+	fn collect_value(&mut self, call: &'static str, value: Value) -> Result {
+		self.before_value(call)?;
+		if let Some(ref mut values) = self.values {
+			values.value(value);
+		}
+		self.after_value()
+	}
+
 	fn indent(&mut self) -> Result {
-		if self.indent_size > 0 {
-			write_bytes!(self, b"\n");
-			let need = (self.stack.len() - 1) * self.indent_size;
-			let indent = if self.tab_indent { TABS } else { SPACES };
-			let avail = indent.len();
-			if need < avail {
-				write_bytes!(self, &indent[..need]);
-			} else {
-				let blocks = need / avail;
-				for _ in 0..blocks {
-					write_bytes!(self, &indent);
-				}
-				write_bytes!(self, &indent[..(need - blocks * avail)]);
-			}
+		match self.pretty {
+			Some(ref config) if config.line_ending != LineEnding::None =>
+				write_line_break(&mut self.writer, config, self.stack.len() - 1),
+			_ => Ok(())
 		}
+	}
 
+	fn begin_color(&mut self, style: Style) -> Result {
+		if let Some(ref colors) = self.colors {
+			write_bytes!(self, colors.sequence(style).as_bytes());
+		}
 		Ok(())
 	}
 
-	fn after_value(&mut self) {
+	fn end_color(&mut self, style: Style) -> Result {
+		if let Some(ref colors) = self.colors {
+			if !colors.sequence(style).is_empty() {
+				write_bytes!(self, colors.reset.as_bytes());
+			}
+		}
+		Ok(())
+	}
+
+	fn punctuation(&mut self, bytes: &[u8]) -> Result {
+		self.begin_color(Style::Punctuation)?;
+		write_bytes!(self, bytes);
+		self.end_color(Style::Punctuation)
+	}
+
+	fn comma(&mut self) -> Result {
+		match self.pretty {
+			Some(ref config) => {
+				let before = config.space_before_comma;
+				let after = config.space_after_comma && config.line_ending == LineEnding::None;
+				if before {
+					write_bytes!(self, b" ");
+				}
+				self.punctuation(b",")?;
+				if after {
+					write_bytes!(self, b" ");
+				}
+				Ok(())
+			},
+			None => self.punctuation(b",")
+		}
+	}
+
+	fn colon(&mut self) -> Result {
+		match self.pretty {
+			Some(ref config) => {
+				let (before, after) = (config.space_before_colon, config.space_after_colon);
+				if before {
+					write_bytes!(self, b" ");
+				}
+				self.punctuation(b":")?;
+				if after {
+					write_bytes!(self, b" ");
+				}
+				Ok(())
+			},
+			None => self.punctuation(b":")
+		}
+	}
+
+	fn write_open(&mut self, bracket: u8) -> Result {
+		if let (Some(layout), Some(config)) = (self.layout.as_mut(), self.pretty.as_ref()) {
+			return layout.open(bracket, config, self.colors.as_ref(), &mut self.writer);
+		}
+		self.punctuation(&[bracket])
+	}
+
+	// the array or object is already popped
+	fn write_close(&mut self, bracket: u8, empty: bool) -> Result {
+		if let Some(ref mut values) = self.values {
+			values.end();
+		}
+		if let (Some(layout), Some(config)) = (self.layout.as_mut(), self.pretty.as_ref()) {
+			return layout.close(bracket, config, self.colors.as_ref(), &mut self.writer);
+		}
+
+		if !empty {
+			if self.trailing_commas() {
+				if let Some(ref config) = self.pretty {
+					if config.line_ending != LineEnding::None {
+						self.punctuation(b",")?;
+					}
+				}
+			}
+			self.indent()?;
+		} else {
+			match self.pretty {
+				Some(ref config) if config.empty_style == EmptyStyle::Space => {
+					write_bytes!(self, b" ");
+				},
+				Some(ref config) if config.empty_style == EmptyStyle::Expanded => {
+					self.indent()?;
+				},
+				_ => {}
+			}
+		}
+		self.punctuation(&[bracket])
+	}
+
+	fn after_value(&mut self) -> Result {
+		if let (Some(layout), Some(config)) = (self.layout.as_mut(), self.pretty.as_ref()) {
+			if self.writer.is_capturing() {
+				layout.value(config, self.colors.as_ref(), &mut self.writer)?;
+			}
+		}
+
 		let i = self.stack.len() - 1;
 		match self.stack[i] {
 			State::ArrayFirstElement => {
 				self.stack[i] = State::ArrayElement;
+				self.path.next_index();
+			},
+
+			State::ArrayElement => {
+				self.path.next_index();
 			},
 
 			State::ObjectValue => {
@@ -206,49 +899,89 @@ impl<'a> JSONBuilder<'a> {
 			
 			State::Begin => {
 				self.stack[i] = State::End;
+				if let Some(ref config) = self.pretty {
+					if config.trailing_newline {
+						let line_ending = match config.line_ending {
+							LineEnding::None => LineEnding::LF,
+							line_ending => line_ending
+						};
+						write_bytes!(self, line_ending.as_str().as_bytes());
+					}
+					if let Some(ref mut layout) = self.layout {
+						layout.end_document(config.trailing_newline);
+					}
+				}
 			},
 
 			_ => {}
 		}
+
+		match self.auto_flush {
+			AutoFlush::TopLevelValue if self.stack.len() == 1 => self.flush(),
+			AutoFlush::RootElement if self.stack.len() <= 2 => self.flush(),
+			_ => Ok(())
+		}
 	}
 
 	pub fn value<Value: IntoJSON>(&mut self, value: Value) -> Result {
-		value.into_json(self)
+		value.write_json(self)
 	}
 
 	pub fn null(&mut self) -> Result {
-		self.before_value()?;
+		if self.values.is_some() {
+			return self.collect_value("null", Value::Null);
+		}
+		self.before_value("null")?;
+		self.begin_color(Style::Null)?;
 		write_bytes!(self, b"null");
-		self.after_value();
+		self.end_color(Style::Null)?;
+		self.after_value()?;
 		Ok(())
 	}
 
 	pub fn key(&mut self, key: &str) -> Result {
 		let i = self.stack.len() - 1;
-		match self.stack[i] {
+		let first = match self.stack[i] {
 			State::ObjectFirstKey => {
-				self.stack[i] = State::ObjectValue;
+				self.path.push_key(key);
+				true
 			},
 
 			State::ObjectKey => {
-				write_bytes!(self, b",");
-				self.stack[i] = State::ObjectValue;
+				self.path.set_key(key);
+				false
 			},
 
-			_ => return Err(Error::State(self.stack[i], vec![
-				State::ObjectFirstKey, State::ObjectKey
-			]))
+			_ => return Err(self.state_error("key", EXPECT_KEY))
+		};
+		self.stack[i] = State::ObjectValue;
+
+		if let Some(ref mut values) = self.values {
+			values.key(key);
 		}
 
-		self.indent()?;
-		write_string!(self, key);
-		if self.indent_size > 0 {
-			write_bytes!(self, b": ");
-		} else {
-			write_bytes!(self, b":");
+		if let (Some(layout), Some(config)) = (self.layout.as_mut(), self.pretty.as_ref()) {
+			layout.begin_capture(&mut self.writer);
+			if let Some(ref colors) = self.colors {
+				write_bytes!(self, colors.key.as_bytes());
+				write_key!(self, key);
+				if !colors.key.is_empty() {
+					write_bytes!(self, colors.reset.as_bytes());
+				}
+			} else {
+				write_key!(self, key);
+			}
+			return layout.key(config, self.colors.as_ref(), &mut self.writer);
 		}
 
-		Ok(())
+		if !first {
+			self.comma()?;
+		}
+		self.indent()?;
+		self.begin_color(Style::Key)?;
+		write_key!(self, key);
+		self.end_color(Style::Key)?;
+		self.colon()
 	}
 
 	pub fn item<Value : IntoJSON>(&mut self, key: &str, value: Value) -> Result {
@@ -257,10 +990,13 @@ impl<'a> JSONBuilder<'a> {
 	}
 
 	pub fn begin_array(&mut self) -> Result {
-		self.before_value()?;
+		self.before_value("begin_array")?;
+		if let Some(ref mut values) = self.values {
+			values.begin(Value::Array(Vec::new()));
+		}
 		self.stack.push(State::ArrayFirstElement);
-		write_bytes!(self, b"[");
-		Ok(())
+		self.path.push_index(0);
+		self.write_open(b'[')
 	}
 
 	pub fn end_array(&mut self) -> Result {
@@ -268,30 +1004,31 @@ impl<'a> JSONBuilder<'a> {
 		match self.stack[i] {
 			State::ArrayElement => {
 				self.stack.pop();
-				self.indent()?;
-				write_bytes!(self, b"]");
-				self.after_value();
+				self.path.pop();
+				self.write_close(b']', false)?;
+				self.after_value()?;
 			},
 
 			State::ArrayFirstElement => {
 				self.stack.pop();
-				write_bytes!(self, b"]");
-				self.after_value();
+				self.path.pop();
+				self.write_close(b']', true)?;
+				self.after_value()?;
 			},
 
-			_ => return Err(Error::State(self.stack[i], vec![
-				State::ArrayElement, State::ArrayFirstElement
-			]))
+			_ => return Err(self.state_error("end_array", EXPECT_END_ARRAY))
 		}
 
 		Ok(())
 	}
 
 	pub fn begin_object(&mut self) -> Result {
-		self.before_value()?;
+		self.before_value("begin_object")?;
+		if let Some(ref mut values) = self.values {
+			values.begin(Value::Object(Map::new()));
+		}
 		self.stack.push(State::ObjectFirstKey);
-		write_bytes!(self, b"{");
-		Ok(())
+		self.write_open(b'{')
 	}
 
 	pub fn end_object(&mut self) -> Result {
@@ -300,21 +1037,19 @@ impl<'a> JSONBuilder<'a> {
 			State::ObjectKey =>
 				{
 					self.stack.pop();
-					self.indent()?;
-					write_bytes!(self, b"}");
-					self.after_value();
+					self.path.pop();
+					self.write_close(b'}', false)?;
+					self.after_value()?;
 				},
 
 			State::ObjectFirstKey =>
 				{
 					self.stack.pop();
-					write_bytes!(self, b"}");
-					self.after_value();
+					self.write_close(b'}', true)?;
+					self.after_value()?;
 				},
 
-			_ => return Err(Error::State(self.stack[i], vec![
-				State::ObjectKey, State::ObjectFirstKey
-			]))
+			_ => return Err(self.state_error("end_object", EXPECT_END_OBJECT))
 		}
 
 		Ok(())
@@ -325,80 +1060,254 @@ impl<'a> JSONBuilder<'a> {
 		let current = self.stack[n - 1];
 
 		if n != 1 || current != State::End {
-			return Err(Error::State(current, vec![State::End]));
+			return Err(self.state_error("end", EXPECT_END));
 		}
 
 		Ok(())
 	}
+
+	// Closes all open arrays and objects, innermost first, and flushes the
+	// output. A key that is still missing its value gets null.
+	pub fn finish(&mut self) -> Result {
+		self.close_to(0)?;
+		self.end()?;
+		self.flush()
+	}
+
+	// closes arrays and objects until depth of them are left open
+	pub(crate) fn close_to(&mut self, depth: usize) -> Result {
+		while self.depth() > depth {
+			match self.state() {
+				State::ArrayFirstElement | State::ArrayElement => self.end_array()?,
+				State::ObjectFirstKey | State::ObjectKey => self.end_object()?,
+				State::ObjectValue => self.null()?,
+				State::Begin | State::End => break
+			}
+		}
+		Ok(())
+	}
+
+	// Starts the next document, e.g. for newline delimited JSON. Whatever
+	// was written of the current document is left as it is, output held
+	// back for the pretty config is dropped.
+	pub fn reset(&mut self) {
+		if let Some(ref mut layout) = self.layout {
+			layout.reset(&mut self.writer);
+		}
+		if let Some(ref mut values) = self.values {
+			values.reset();
+		}
+		self.stack.clear();
+		self.stack.push(State::Begin);
+		self.path = JSONPath::new();
+	}
+}
+
+#[cfg(feature = "std")]
+impl<W: Sink + std::io::IsTerminal> JSONBuilder<W> {
+	// Like set_color_scheme(colors.if_terminal(builder.get_ref())), so the
+	// colors are only used if the writer of this builder is a terminal.
+	pub fn set_color_scheme_if_terminal(&mut self, colors: ColorScheme) {
+		let colors = colors.if_terminal(self.get_ref());
+		self.set_color_scheme(colors);
+	}
 }
 
-macro_rules! impl_into_json_for_primitive {
+impl IntoJSON for bool {
+	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		if builder.values.is_some() {
+			return builder.collect_value("value", Value::from(*self));
+		}
+		builder.before_value("value")?;
+		builder.begin_color(Style::Boolean)?;
+		write_bytes!(builder, if *self { b"true" } else { b"false" });
+		builder.end_color(Style::Boolean)?;
+		builder.after_value()?;
+		Ok(())
+	}
+}
+
+macro_rules! impl_into_json_for_integer {
 	($($t:ty),+) => {
 		$(impl IntoJSON for $t {
 			fn into_json(&self, builder: &mut JSONBuilder) -> Result {
-				builder.before_value()?;
-				match write!(builder.writer, "{}", self) {
-					Err(err) => return Err(Error::IO(err)),
+				self.write_json(builder)
+			}
+
+			fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+				if builder.values.is_some() {
+					return builder.collect_value("value", Value::from(*self));
+				}
+				builder.before_value("value")?;
+				builder.begin_color(Style::Number)?;
+				match write_integer(*self, &mut builder.writer) {
+					Err(err) => return Err(err),
 					_ => {}
 				}
-				builder.after_value();
+				builder.end_color(Style::Number)?;
+				builder.after_value()?;
 				Ok(())
 			}
 		})*
 	}
 }
 
-impl_into_json_for_primitive!{
-	bool,
+impl_into_json_for_integer!{
 	i8, i16, i32, i64, i128,
 	u8, u16, u32, u64, u128,
-	isize, usize,
+	isize, usize
+}
+
+macro_rules! impl_into_json_for_float {
+	($($t:ty),+) => {
+		$(impl IntoJSON for $t {
+			fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+				self.write_json(builder)
+			}
+
+			fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+				if !self.is_finite() {
+					return builder.non_finite(*self as f64);
+				}
+				if builder.values.is_some() {
+					return builder.collect_value("value", Value::from(*self));
+				}
+				builder.before_value("value")?;
+				builder.begin_color(Style::Number)?;
+				match write_float(*self, &builder.float_format, &mut builder.writer) {
+					Err(err) => return Err(err),
+					_ => {}
+				}
+				builder.end_color(Style::Number)?;
+				builder.after_value()?;
+				Ok(())
+			}
+		})*
+	}
+}
+
+impl_into_json_for_float!{
 	f32, f64
 }
 
-impl<'a, T: IntoJSON> IntoJSON for &'a T {
+macro_rules! impl_into_json_for_hex {
+	($($t:ty),+) => {
+		$(impl IntoJSON for Hex<$t> {
+			fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+				self.write_json(builder)
+			}
+
+			fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+				if builder.values.is_some() {
+					return builder.collect_value("value", Value::from(self.0));
+				}
+				builder.before_value("value")?;
+				builder.begin_color(Style::Number)?;
+				let result = match builder.dialect {
+					Dialect::JSON5 { .. } => write_hex_integer(self.0, &mut builder.writer),
+					Dialect::JSON => write_integer(self.0, &mut builder.writer)
+				};
+				match result {
+					Err(err) => return Err(err),
+					_ => {}
+				}
+				builder.end_color(Style::Number)?;
+				builder.after_value()?;
+				Ok(())
+			}
+		})*
+	}
+}
+
+impl_into_json_for_hex!{
+	i8, i16, i32, i64, i128,
+	u8, u16, u32, u64, u128,
+	isize, usize
+}
+
+impl<T: IntoJSON> IntoJSON for &T {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
 		(*self).into_json(builder)
 	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		(*self).write_json(builder)
+	}
 }
 
-impl<'a> IntoJSON for &'a IntoJSON {
+// for mixing values of different types
+impl IntoJSON for &dyn IntoJSON {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
-		(*self).into_json(builder)
+		(**self).into_json(builder)
 	}
 }
 
-impl<'a> IntoJSON for &'a str {
+impl IntoJSON for &str {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
-		builder.before_value()?;
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		if builder.values.is_some() {
+			return builder.collect_value("value", Value::from(*self));
+		}
+		builder.before_value("value")?;
+		builder.begin_color(Style::String)?;
 		write_string!(builder, self);
-		builder.after_value();
+		builder.end_color(Style::String)?;
+		builder.after_value()?;
 		Ok(())
 	}
 }
 
 impl IntoJSON for String {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
-		builder.before_value()?;
-		write_string!(builder, &self);
-		builder.after_value();
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		if builder.values.is_some() {
+			return builder.collect_value("value", Value::from(self.as_str()));
+		}
+		builder.before_value("value")?;
+		builder.begin_color(Style::String)?;
+		write_string!(builder, self);
+		builder.end_color(Style::String)?;
+		builder.after_value()?;
 		Ok(())
 	}
 }
 
 impl IntoJSON for char {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
-		builder.before_value()?;
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		if builder.values.is_some() {
+			return builder.collect_value("value", Value::from(*self));
+		}
+		builder.before_value("value")?;
+		builder.begin_color(Style::String)?;
 		write_string!(builder, self.to_string().as_str());
-		builder.after_value();
+		builder.end_color(Style::String)?;
+		builder.after_value()?;
 		Ok(())
 	}
 }
 
 impl<T: IntoJSON> IntoJSON for Option<T> {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
 		match self {
-			Some(value) => value.into_json(builder),
+			Some(value) => value.write_json(builder),
 			None => builder.null()
 		}
 	}
@@ -406,12 +1315,20 @@ impl<T: IntoJSON> IntoJSON for Option<T> {
 
 impl<T: IntoJSON> IntoJSON for Box<T> {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
-		(**self).into_json(builder)
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		(**self).write_json(builder)
 	}
 }
 
-impl<'a, T: IntoJSON> IntoJSON for &'a [T] {
+impl<T: IntoJSON> IntoJSON for &[T] {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
 		builder.begin_array()?;
 		for item in *self {
 			builder.value(item)?;
@@ -425,6 +1342,10 @@ macro_rules! impl_into_json_for_map {
 		$(
 			impl<'a, T: IntoJSON> IntoJSON for $t {
 				fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+					self.write_json(builder)
+				}
+
+				fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
 					builder.begin_object()?;
 					for (key, value) in self {
 						builder.key(key)?;
@@ -438,8 +1359,12 @@ macro_rules! impl_into_json_for_map {
 }
 
 impl_into_json_for_map!{
-	std::collections::BTreeMap<String, T>,
-	std::collections::BTreeMap<&'a str, T>,
+	alloc::collections::BTreeMap<String, T>,
+	alloc::collections::BTreeMap<&'a str, T>
+}
+
+#[cfg(feature = "std")]
+impl_into_json_for_map!{
 	std::collections::HashMap<String, T>,
 	std::collections::HashMap<&'a str, T>
 }
@@ -449,6 +1374,10 @@ macro_rules! impl_into_json_for_iterable {
 		$(
 			impl<T: IntoJSON> IntoJSON for $t {
 				fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+					self.write_json(builder)
+				}
+
+				fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
 					builder.begin_array()?;
 					for item in self {
 						builder.value(item)?;
@@ -462,6 +1391,10 @@ macro_rules! impl_into_json_for_iterable {
 /*
 impl<T: IntoJSON> IntoJSON for std::collections::HashSet<T> {
 	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
 		builder.begin_array()?;
 		for item in self.iter() {
 			builder.value(item)?;
@@ -472,11 +1405,11 @@ impl<T: IntoJSON> IntoJSON for std::collections::HashSet<T> {
 */
 impl_into_json_for_iterable!{
 	Vec<T>,
-//	std::collections::BinaryHeap<T>,
-	std::collections::BTreeSet<T>,
+//	alloc::collections::BinaryHeap<T>,
+	alloc::collections::BTreeSet<T>,
 //	std::collections::HashSet<T>,
-	std::collections::LinkedList<T>,
-	std::collections::VecDeque<T>
+	alloc::collections::LinkedList<T>,
+	alloc::collections::VecDeque<T>
 }
 
 macro_rules! impl_into_json_for_array {
@@ -484,6 +1417,10 @@ macro_rules! impl_into_json_for_array {
 		$(
 			impl<T: IntoJSON> IntoJSON for [T; $n] {
 				fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+					self.write_json(builder)
+				}
+
+				fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
 					builder.begin_array()?;
 					for item in self {
 						builder.value(item)?;
@@ -507,7 +1444,8 @@ macro_rules! json_unexpected {
 	() => {};
 }
 
-#[doc(hide)]
+#[macro_export(local_inner_macros)]
+#[doc(hidden)]
 macro_rules! json_internal {
 
 	// inspired by serde: https://github.com/serde-rs/json/blob/master/src/macros.rs
@@ -670,56 +1608,62 @@ macro_rules! json_internal {
 	};
 }
 
-#[macro_export]
+#[macro_export(local_inner_macros)]
 macro_rules! build_json {
 	($writer:expr, $($json:tt)+) => {
 		loop {
-			let mut builder = JSONBuilder::new($writer);
+			let mut builder = $crate::JSONBuilder::new($writer);
 			json_internal!(builder $($json)+);
 			break builder.end();
 		}
 	}
 }
 
+#[macro_export(local_inner_macros)]
 #[doc(hidden)]
 macro_rules! pretty_json_internal {
-	($indent_size:expr, $tab_indent:expr, $($json:tt)+) => {
+	($config:expr, $($json:tt)+) => {
 		loop {
-			let mut data = Vec::<u8>::new();
-			{
-				let mut builder = JSONBuilder::new_pretty(&mut data, $indent_size, $tab_indent);
-				json_internal!(builder $($json)+);
-				match builder.end() { Err(err) => break Err(err), _ => {} }
-			}
-			break Ok(String::from_utf8(data).unwrap());
+			let mut builder = $crate::JSONBuilder::new_fmt($crate::__private::String::new());
+			builder.set_pretty_config($config);
+			json_internal!(builder $($json)+);
+			match builder.end() { Err(err) => break Err(err), _ => {} }
+			break match builder.into_inner() {
+				Ok(writer) => Ok(writer.into_inner()),
+				Err(err) => Err(err)
+			};
 		}
 	};
 }
 
-#[macro_export]
+#[macro_export(local_inner_macros)]
 macro_rules! json {
+	(config($config:expr) $($json:tt)+) => {
+		pretty_json_internal!(::core::option::Option::Some($config), $($json)+)
+	};
+
 	(spaces($indent_size:expr) $($json:tt)+) => {
-		pretty_json_internal!($indent_size, false, $($json)+)
+		pretty_json_internal!($crate::PrettyConfig::from_indent($indent_size, false), $($json)+)
 	};
 
 	(tabs($indent_size:expr) $($json:tt)+) => {
-		pretty_json_internal!($indent_size, true, $($json)+)
+		pretty_json_internal!($crate::PrettyConfig::from_indent($indent_size, true), $($json)+)
 	};
 
 	(spaces $($json:tt)+) => {
-		pretty_json_internal!(4, false, $($json)+)
+		pretty_json_internal!($crate::PrettyConfig::from_indent(4, false), $($json)+)
 	};
 
 	(tabs $($json:tt)+) => {
-		pretty_json_internal!(1, true, $($json)+)
+		pretty_json_internal!($crate::PrettyConfig::from_indent(1, true), $($json)+)
 	};
 
 	(pretty $($json:tt)+) => {
-		pretty_json_internal!(1, true, $($json)+)
+		pretty_json_internal!($crate::PrettyConfig::from_indent(1, true), $($json)+)
 	};
 
 	($($json:tt)+) => {
-		pretty_json_internal!(0, true, $($json)+)
+		pretty_json_internal!(::core::option::Option::None, $($json)+)
 	};
 }
 
@@ -791,19 +1735,548 @@ macro_rules! impl_into_json_internal {
 	};
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_into_json_internal_variant {
+	// unit variants are always written as plain strings
+	($b:ident $repr:tt $name:expr, ()) => {
+		$b.value($name)?;
+	};
+
+	($b:ident (untagged) $name:expr, {$($field:ident)*}) => {
+		$b.begin_object()?;
+		$($b.item(stringify!($field), $field)?;)*
+		$b.end_object()?;
+	};
+
+	($b:ident (untagged) $name:expr, ($field:ident)) => {
+		$b.value($field)?;
+	};
+
+	($b:ident (untagged) $name:expr, ($($field:ident)*)) => {
+		$b.begin_array()?;
+		$($b.value($field)?;)*
+		$b.end_array()?;
+	};
+
+	($b:ident (external) $name:expr, $fields:tt) => {
+		$b.begin_object()?;
+		$b.key($name)?;
+		$crate::impl_into_json_internal_variant!($b (untagged) $name, $fields);
+		$b.end_object()?;
+	};
+
+	($b:ident (internal $tag:expr) $name:expr, {$($field:ident)*}) => {
+		$b.begin_object()?;
+		$b.item($tag, $name)?;
+		$($b.item(stringify!($field), $field)?;)*
+		$b.end_object()?;
+	};
+
+	($b:ident (internal $tag:expr) $name:expr, ($($field:ident)*)) => {
+		compile_error!("internally tagged enums only support struct and unit variants");
+	};
+
+	($b:ident (adjacent $tag:expr, $content:expr) $name:expr, $fields:tt) => {
+		$b.begin_object()?;
+		$b.item($tag, $name)?;
+		$b.key($content)?;
+		$crate::impl_into_json_internal_variant!($b (untagged) $name, $fields);
+		$b.end_object()?;
+	};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_into_json_internal_enum {
+	($b:ident $s:ident $repr:tt [$($arms:tt)*]) => {
+		match $s {
+			$($arms)*
+		}
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] , $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*] $($more)*);
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] $v:ident { $($field:ident),* $(,)? } as $name:tt $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*
+			Self::$v { $($field,)* .. } => {
+				$crate::impl_into_json_internal_variant!($b $repr $name, {$($field)*});
+			}
+		] $($more)*);
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] $v:ident ( $($field:ident),* $(,)? ) as $name:tt $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*
+			Self::$v ( $($field),* ) => {
+				$crate::impl_into_json_internal_variant!($b $repr $name, ($($field)*));
+			}
+		] $($more)*);
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] $v:ident as $name:tt $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*
+			Self::$v => {
+				$crate::impl_into_json_internal_variant!($b $repr $name, ());
+			}
+		] $($more)*);
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] $v:ident { $($field:tt)* } $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*] $v { $($field)* } as (stringify!($v)) $($more)*);
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] $v:ident ( $($field:tt)* ) $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*] $v ( $($field)* ) as (stringify!($v)) $($more)*);
+	};
+
+	($b:ident $s:ident $repr:tt [$($arms:tt)*] $v:ident $($more:tt)*) => {
+		$crate::impl_into_json_internal_enum!($b $s $repr [$($arms)*] $v as (stringify!($v)) $($more)*);
+	};
+}
+
 #[macro_export(local_inner_macros)]
 macro_rules! impl_into_json {
+	(enum $t:ty [untagged], $($variants:tt)*) => {
+		impl_into_json!(@enum $t, (untagged), $($variants)*);
+	};
+
+	(enum $t:ty [tag = $tag:expr, content = $content:expr], $($variants:tt)*) => {
+		impl_into_json!(@enum $t, (adjacent $tag, $content), $($variants)*);
+	};
+
+	(enum $t:ty [tag = $tag:expr], $($variants:tt)*) => {
+		impl_into_json!(@enum $t, (internal $tag), $($variants)*);
+	};
+
+	(enum $t:ty, $($variants:tt)*) => {
+		impl_into_json!(@enum $t, (external), $($variants)*);
+	};
+
+	(@enum $t:ty, $repr:tt, $($variants:tt)*) => {
+		impl $crate::IntoJSON for $t {
+			fn into_json(&self, builder: &mut $crate::JSONBuilder) -> $crate::Result {
+				$crate::IntoJSON::write_json(self, builder)
+			}
+
+			fn write_json<W: $crate::Sink>(&self, builder: &mut $crate::JSONBuilder<W>) -> $crate::Result {
+				impl_into_json_internal_enum!(builder self $repr [] $($variants)*);
+				Ok(())
+			}
+		}
+	};
+
 	($t:ty) => {
 		impl_into_json!($t, );
 	};
 
 	($t:ty, $($def:tt)*) => {
-		impl IntoJSON for $t {
-			fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		impl $crate::IntoJSON for $t {
+			fn into_json(&self, builder: &mut $crate::JSONBuilder) -> $crate::Result {
+				$crate::IntoJSON::write_json(self, builder)
+			}
+
+			fn write_json<W: $crate::Sink>(&self, builder: &mut $crate::JSONBuilder<W>) -> $crate::Result {
 				builder.begin_object()?;
 				impl_into_json_internal!(builder self () ($($def)*));
 				builder.end_object()
 			}
 		}
 	};
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn escaped(s: &str, mode: EscapeMode) -> String {
+		let mut builder = JSONBuilder::new_fmt(String::new());
+		builder.set_escape_mode(mode);
+		builder.value(s).unwrap();
+		builder.into_inner().unwrap().into_inner()
+	}
+
+	#[test]
+	fn escapes_by_mode() {
+		let s = "<a href='/'>&\"\\\t\u{1}\u{2028}é😀";
+		assert_eq!(EscapeMode::default(), EscapeMode::Minimal);
+		assert_eq!(escaped(s, EscapeMode::Minimal), "\"<a href='/'>&\\\"\\\\\\t\\u0001\u{2028}é😀\"");
+		assert_eq!(escaped(s, EscapeMode::HtmlSafe),
+			"\"\\u003ca href=\\u0027\\/\\u0027\\u003e\\u0026\\\"\\\\\\t\\u0001\u{2028}é😀\"");
+		assert_eq!(escaped("\u{2028}\u{2029}", EscapeMode::JavaScriptSafe), "\"\\u2028\\u2029\"");
+		assert_eq!(escaped("é😀", EscapeMode::AsciiOnly), "\"\\u00e9\\ud83d\\ude00\"");
+
+		let mut output = Vec::new();
+		escape_json("<\n>", &mut output).unwrap();
+		assert_eq!(output, b"<\\n>");
+	}
+
+	#[test]
+	fn writes_floats_with_format() {
+		let mut builder = JSONBuilder::new_fmt(String::new());
+		builder.set_float_format(FloatFormat { always_fraction: true, ..FloatFormat::javascript() });
+		builder.value(&[1.0f32, 0.1, 1e21][..]).unwrap();
+		assert_eq!(builder.into_inner().unwrap().into_inner(), "[1.0,0.1,1.0e21]");
+	}
+
+	struct Labeled(&'static str);
+
+	impl IntoJSON for Labeled {
+		fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+			self.write_json(builder)
+		}
+
+		fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+			builder.begin_object()?;
+			builder.item("label", self.0)?;
+			builder.end_object()
+		}
+	}
+
+	fn owned_builder() -> JSONBuilder<Vec<u8>> {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.begin_array().unwrap();
+		builder
+	}
+
+	fn assert_send<T: Send>(_: &T) {}
+
+	#[test]
+	fn owns_or_borrows_its_sink() {
+		let mut builder = owned_builder();
+		assert_send(&builder);
+		builder.value(Labeled("a")).unwrap();
+		builder.end_array().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"[{\"label\":\"a\"}]");
+
+		let mut output = Vec::new();
+		JSONBuilder::new(&mut output).value(1u8).unwrap();
+		assert_eq!(output, b"1");
+
+		let mut builder: JSONBuilder = JSONBuilder::new(Box::new(Vec::new()));
+		builder.value(Labeled("boxed")).unwrap();
+		builder.end().unwrap();
+	}
+
+	#[test]
+	fn writes_through_dyn_builders() {
+		let values: [&dyn IntoJSON; 3] = [&1u8, &"two", &Labeled("three")];
+		let mut builder = JSONBuilder::new_fmt_pretty(String::new(), 1, false);
+		builder.value(&values[..]).unwrap();
+		builder.end().unwrap();
+		assert_eq!(builder.into_inner().unwrap().into_inner(), "[\n 1,\n \"two\",\n {\n  \"label\": \"three\"\n }\n]");
+	}
+
+	// only implements into_json(), like implementations written before the
+	// builder was generic
+	struct Legacy(u8);
+
+	impl IntoJSON for Legacy {
+		fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+			builder.begin_array()?;
+			builder.value(self.0)?;
+			builder.value(&[self.0][..])?;
+			builder.end_array()
+		}
+	}
+
+	#[test]
+	fn writes_legacy_implementations() {
+		let mut output = Vec::new();
+		{
+			let mut builder = JSONBuilder::new_pretty(&mut output, 1, false);
+			builder.set_buffer_capacity(4);
+			builder.begin_object().unwrap();
+			builder.item("legacy", Legacy(1)).unwrap();
+			builder.item("list", &[Legacy(2)][..]).unwrap();
+			builder.end_object().unwrap();
+			builder.end().unwrap();
+		}
+		assert_eq!(String::from_utf8(output).unwrap(),
+			"{\n \"legacy\": [\n  1,\n  [\n   1\n  ]\n ],\n \"list\": [\n  [\n   2,\n   [\n    2\n   ]\n  ]\n ]\n}");
+		assert_eq!(Legacy(3).to_json().unwrap(), "[3,[3]]");
+		assert_eq!(Legacy(4).to_value().unwrap(), Value::Array(vec![Value::from(4u8), Value::Array(vec![Value::from(4u8)])]));
+	}
+
+	#[test]
+	fn restores_settings_after_dyn() {
+		let format = FloatFormat { always_fraction: true, ..FloatFormat::default() };
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.with_dyn(|builder| {
+			builder.set_dialect(Dialect::JSON5 { single_quotes: true, trailing_commas: false });
+			builder.set_escape_mode(EscapeMode::AsciiOnly);
+			builder.set_non_finite_policy(NonFinitePolicy::Null);
+			builder.set_float_format(format);
+			builder.set_auto_flush(AutoFlush::RootElement);
+			Ok(())
+		}).unwrap();
+		assert_eq!(builder.dialect(), Dialect::JSON5 { single_quotes: true, trailing_commas: false });
+		assert_eq!(builder.escape_mode(), EscapeMode::AsciiOnly);
+		assert_eq!(builder.non_finite_policy(), NonFinitePolicy::Null);
+		assert_eq!(builder.float_format(), format);
+		assert_eq!(builder.auto_flush(), AutoFlush::RootElement);
+
+		builder.with_boxed(|builder| {
+			builder.set_escape_mode(EscapeMode::HtmlSafe);
+			builder.value(&[1.0, f64::NAN][..])
+		}).unwrap();
+		assert_eq!(builder.escape_mode(), EscapeMode::HtmlSafe);
+		assert_eq!(builder.into_inner().unwrap(), b"[1.0,null]");
+	}
+
+	struct Failing;
+
+	impl IntoJSON for Failing {
+		fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+			builder.begin_object()?;
+			builder.end_array()
+		}
+	}
+
+	#[test]
+	fn restores_state_after_dyn_errors() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.set_buffer_capacity(64);
+		builder.begin_array().unwrap();
+		builder.value(&Failing as &dyn IntoJSON).unwrap_err();
+		assert_eq!(builder.depth(), 2);
+		assert_eq!(builder.path().to_string(), "$[0]");
+		builder.end_object().unwrap();
+		builder.end_array().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"[{}]");
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn restores_state_after_dyn_panics() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.begin_array().unwrap();
+		builder.value(1u8).unwrap();
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			builder.with_dyn(|builder| {
+				builder.begin_object()?;
+				panic!("in with_dyn")
+			})
+		}));
+		assert!(result.is_err());
+		assert_eq!(builder.depth(), 2);
+		assert_eq!(builder.state(), State::ObjectFirstKey);
+		assert!(builder.value(2u8).is_err());
+		builder.finish().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"[1,{}]");
+	}
+
+	#[test]
+	fn tracks_path_and_state() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		assert_eq!((builder.depth(), builder.state(), builder.expects_value()), (0, State::Begin, true));
+		builder.begin_object().unwrap();
+		assert_eq!((builder.depth(), builder.state(), builder.expects_value()), (1, State::ObjectFirstKey, false));
+		builder.key("users").unwrap();
+		assert_eq!(builder.state(), State::ObjectValue);
+		assert_eq!(builder.path().to_string(), "$.users");
+		builder.begin_array().unwrap();
+		builder.value(1).unwrap();
+		builder.begin_object().unwrap();
+		builder.key("first name").unwrap();
+		assert_eq!(builder.depth(), 3);
+		assert_eq!(builder.path().to_string(), "$.users[1][\"first name\"]");
+		builder.value("Ann").unwrap();
+		builder.end_object().unwrap();
+		// where the next element goes
+		assert_eq!(builder.path().to_string(), "$.users[2]");
+		builder.end_array().unwrap();
+		builder.end_object().unwrap();
+		assert_eq!((builder.depth(), builder.state(), builder.expects_value()), (0, State::End, false));
+		assert!(builder.path().is_empty());
+	}
+
+	#[test]
+	fn reports_state_errors_with_path() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.begin_object().unwrap();
+		builder.key("list").unwrap();
+		builder.begin_array().unwrap();
+		builder.value(true).unwrap();
+		let err = builder.key("x").unwrap_err();
+		match err {
+			Error::State { call, state, expected, ref path } => {
+				assert_eq!(call, "key");
+				assert_eq!(state, State::ArrayElement);
+				assert_eq!(expected, EXPECT_KEY);
+				assert_eq!(path.to_string(), "$.list[1]");
+			},
+			ref other => panic!("unexpected error: {:?}", other)
+		}
+		assert_eq!(err.to_string(), "called key() while an array element was expected at $.list[1]");
+		assert_eq!(builder.end_object().unwrap_err().to_string(), "called end_object() while an array element was expected at $.list[1]");
+		builder.end_array().unwrap();
+		builder.end_object().unwrap();
+		assert_eq!(builder.value(1).unwrap_err().to_string(), "called value() while the end of the document was expected at $");
+	}
+
+	fn non_finite(policy: NonFinitePolicy) -> core::result::Result<String, Error> {
+		let mut builder = JSONBuilder::new_fmt(String::new());
+		builder.set_non_finite_policy(policy);
+		builder.value(&[f64::NAN, f64::NEG_INFINITY][..])?;
+		Ok(builder.into_inner()?.into_inner())
+	}
+
+	#[test]
+	fn writes_non_finite_floats() {
+		assert_eq!(non_finite(NonFinitePolicy::String).unwrap(), "[\"NaN\",\"-Infinity\"]");
+		assert_eq!(non_finite(NonFinitePolicy::Null).unwrap(), "[null,null]");
+		assert_eq!(non_finite(NonFinitePolicy::JSON5).unwrap(), "[NaN,-Infinity]");
+		match non_finite(NonFinitePolicy::Error) {
+			Err(Error::NonFinite { policy, value, state, ref path }) => {
+				assert_eq!(policy, NonFinitePolicy::Error);
+				assert!(value.is_nan());
+				assert_eq!(state, State::ArrayFirstElement);
+				assert_eq!(path.to_string(), "$[0]");
+			},
+			ref other => panic!("unexpected result: {:?}", other)
+		}
+
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.begin_object().unwrap();
+		match builder.item("x", f32::INFINITY) {
+			Err(Error::NonFinite { value, state, ref path, .. }) => {
+				assert_eq!(value, f64::INFINITY);
+				assert_eq!(state, State::ObjectValue);
+				assert_eq!(path.to_string(), "$.x");
+			},
+			ref other => panic!("unexpected result: {:?}", other)
+		}
+	}
+
+	#[test]
+	fn displays_errors() {
+		assert_eq!(non_finite(NonFinitePolicy::Error).unwrap_err().to_string(),
+			"cannot write NaN as an array element at $[0], non-finite numbers are not allowed by NonFinitePolicy::Error");
+
+		let err = Error::Fmt(fmt::Error);
+		assert_eq!(err.to_string(), "formatting error");
+		assert!(core::error::Error::source(&err).unwrap().is::<fmt::Error>());
+
+		let err = Error::BufferFull;
+		assert_eq!(err.to_string(), "output buffer is full");
+		assert!(core::error::Error::source(&err).is_none());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn displays_io_errors() {
+		let err = Error::from(std::io::Error::other("gone"));
+		assert_eq!(err.to_string(), "I/O error: gone");
+		let source = core::error::Error::source(&err).unwrap();
+		assert_eq!(source.downcast_ref::<std::io::Error>().unwrap().to_string(), "gone");
+	}
+
+	fn json5(dialect: Dialect, config: Option<PrettyConfig>) -> String {
+		let mut builder = JSONBuilder::new_fmt(String::new());
+		builder.set_pretty_config(config);
+		builder.set_dialect(dialect);
+		builder.begin_object().unwrap();
+		builder.item("id", Hex(255u32)).unwrap();
+		builder.item("$min", Hex(-16i8)).unwrap();
+		builder.item("größe", "ß").unwrap();
+		builder.item("2nd key", "it's \"quoted\"").unwrap();
+		builder.item("list", &[f64::NAN, f64::INFINITY, -f64::INFINITY][..]).unwrap();
+		builder.key("empty").unwrap();
+		builder.begin_array().unwrap();
+		builder.end_array().unwrap();
+		builder.end_object().unwrap();
+		builder.into_inner().unwrap().into_inner()
+	}
+
+	#[test]
+	fn writes_json5() {
+		assert_eq!(json5(Dialect::json5(), None), "{id:0xff,$min:-0x10,größe:\"ß\",\"2nd key\":\"it's \\\"quoted\\\"\",list:[NaN,Infinity,-Infinity],empty:[]}");
+		assert_eq!(json5(Dialect::JSON5 { single_quotes: true, trailing_commas: false }, None), "{id:0xff,$min:-0x10,größe:'ß','2nd key':'it\\'s \"quoted\"',list:[NaN,Infinity,-Infinity],empty:[]}");
+	}
+
+	#[test]
+	fn writes_json5_trailing_commas() {
+		assert_eq!(json5(Dialect::json5(), Some(PrettyConfig::spaces(2))),
+			"{\n  id: 0xff,\n  $min: -0x10,\n  größe: \"ß\",\n  \"2nd key\": \"it's \\\"quoted\\\"\",\n  list: [\n    NaN,\n    Infinity,\n    -Infinity,\n  ],\n  empty: [],\n}");
+		assert_eq!(json5(Dialect::json5(), Some(PrettyConfig { max_width: 40, ..PrettyConfig::spaces(2) })),
+			"{\n  id: 0xff,\n  $min: -0x10,\n  größe: \"ß\",\n  \"2nd key\": \"it's \\\"quoted\\\"\",\n  list: [NaN, Infinity, -Infinity],\n  empty: [],\n}");
+	}
+
+	#[test]
+	fn writes_plain_json_without_json5_syntax() {
+		let mut builder = JSONBuilder::new_fmt(String::new());
+		builder.begin_object().unwrap();
+		builder.item("id", Hex(255u32)).unwrap();
+		assert!(builder.item("nan", f64::NAN).is_err());
+		assert_eq!(builder.into_inner().unwrap().into_inner(), "{\"id\":255,\"nan\":");
+	}
+
+	macro_rules! test_enums {
+		($($name:ident),+) => {
+			$(enum $name {
+				Tuple(u8, bool),
+				Newtype(&'static str),
+				Struct { a: u8, b: u8 },
+				Unit
+			})+
+		}
+	}
+
+	test_enums!(External, Adjacent, Untagged);
+
+	const NEWTYPE: &str = "new";
+
+	impl_into_json! {
+		enum External,
+		Tuple(a, b,),
+		Newtype(value) as "new",
+		Struct { a, b, },
+		Unit as "unit"
+	}
+
+	impl_into_json! {
+		enum Adjacent [tag = "t", content = "c"],
+		Tuple(a, b),
+		Newtype(value) as (NEWTYPE),
+		Struct { a, b } as "struct",
+		Unit
+	}
+
+	impl_into_json! {
+		enum Untagged [untagged],
+		Tuple(a, b),
+		Newtype(value),
+		Struct { a, b },
+		Unit
+	}
+
+	enum Internal {
+		Struct { a: u8, b: u8 },
+		Empty {},
+		Unit
+	}
+
+	impl_into_json! {
+		enum Internal [tag = "type"],
+		Struct { a, b } as "struct",
+		Empty {},
+		Unit
+	}
+
+	fn variants<T: IntoJSON>(values: [T; 4]) -> String {
+		(&values[..]).to_json().unwrap()
+	}
+
+	#[test]
+	fn writes_enum_representations() {
+		assert_eq!(variants([External::Tuple(1, true), External::Newtype("x"), External::Struct { a: 1, b: 2 }, External::Unit]),
+			"[{\"Tuple\":[1,true]},{\"new\":\"x\"},{\"Struct\":{\"a\":1,\"b\":2}},\"unit\"]");
+		assert_eq!(variants([Adjacent::Tuple(1, true), Adjacent::Newtype("x"), Adjacent::Struct { a: 1, b: 2 }, Adjacent::Unit]),
+			"[{\"t\":\"Tuple\",\"c\":[1,true]},{\"t\":\"new\",\"c\":\"x\"},{\"t\":\"struct\",\"c\":{\"a\":1,\"b\":2}},\"Unit\"]");
+		assert_eq!(variants([Untagged::Tuple(1, true), Untagged::Newtype("x"), Untagged::Struct { a: 1, b: 2 }, Untagged::Unit]),
+			"[[1,true],\"x\",{\"a\":1,\"b\":2},\"Unit\"]");
+		assert_eq!(variants([Internal::Struct { a: 1, b: 2 }, Internal::Empty {}, Internal::Unit, Internal::Struct { a: 3, b: 4 }]),
+			"[{\"type\":\"struct\",\"a\":1,\"b\":2},{\"type\":\"Empty\"},\"Unit\",{\"type\":\"struct\",\"a\":3,\"b\":4}]");
+	}
+}
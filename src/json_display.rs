@@ -0,0 +1,123 @@
+use core::fmt;
+
+use crate::json_builder::{Error, IntoJSON, JSONBuilder, NonFinitePolicy, Result};
+use crate::pretty_config::PrettyConfig;
+use crate::sink::Sink;
+
+// Adapts a fmt::Write (a String, a fmt::Formatter, ...) as a builder sink.
+// The builder only ever writes complete UTF-8 sequences, anything else is
+// rejected with Error::Fmt.
+pub struct FmtWriter<F> {
+	inner: F
+}
+
+impl<F: fmt::Write> FmtWriter<F> {
+	pub fn new(inner: F) -> FmtWriter<F> {
+		FmtWriter { inner }
+	}
+
+	pub fn get_ref(&self) -> &F {
+		&self.inner
+	}
+
+	pub fn get_mut(&mut self) -> &mut F {
+		&mut self.inner
+	}
+
+	pub fn into_inner(self) -> F {
+		self.inner
+	}
+}
+
+impl<F: fmt::Write> Sink for FmtWriter<F> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		let s = match core::str::from_utf8(bytes) {
+			Ok(s) => s,
+			Err(_) => return Err(Error::Fmt(fmt::Error))
+		};
+		match self.inner.write_str(s) {
+			Ok(()) => Ok(()),
+			Err(err) => Err(Error::Fmt(err))
+		}
+	}
+}
+
+impl<F: fmt::Write> JSONBuilder<FmtWriter<F>> {
+	pub fn new_fmt(writer: F) -> JSONBuilder<FmtWriter<F>> {
+		JSONBuilder::new(FmtWriter::new(writer))
+	}
+
+	pub fn new_fmt_pretty(writer: F, indent_size: usize, tab_indent: bool) -> JSONBuilder<FmtWriter<F>> {
+		JSONBuilder::new_pretty(FmtWriter::new(writer), indent_size, tab_indent)
+	}
+
+	pub fn new_fmt_with_pretty_config(writer: F, config: PrettyConfig) -> JSONBuilder<FmtWriter<F>> {
+		JSONBuilder::new_with_pretty_config(FmtWriter::new(writer), config)
+	}
+}
+
+// Formats a value as JSON: "{}" writes compact output, "{:#}" indents with
+// tabs and "{:#4}" indents with the given number of spaces. NaN and infinite
+// floats are written as null, like JSON.stringify() does, because a failing
+// Display makes to_string() and format!() panic. Only IntoJSON
+// implementations that call the builder methods out of order still fail.
+pub struct JSONDisplay<'a, T: ?Sized + 'a> {
+	value: &'a T
+}
+
+pub fn json_display<'a, T: ?Sized>(value: &'a T) -> JSONDisplay<'a, T> where &'a T: IntoJSON {
+	JSONDisplay { value }
+}
+
+impl<'a, T: ?Sized> fmt::Display for JSONDisplay<'a, T> where &'a T: IntoJSON {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let (indent_size, tab_indent) = if !f.alternate() {
+			(0, true)
+		} else {
+			match f.width() {
+				Some(width) => (width, false),
+				None => (1, true)
+			}
+		};
+		let mut builder = JSONBuilder::new_fmt_pretty(f, indent_size, tab_indent);
+		builder.set_non_finite_policy(NonFinitePolicy::Null);
+		match builder.value(self.value) {
+			Ok(()) => {},
+			Err(_) => return Err(fmt::Error)
+		}
+		match builder.end() {
+			Ok(()) => Ok(()),
+			Err(_) => Err(fmt::Error)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::{String, ToString};
+	use alloc::{format, vec};
+
+	#[test]
+	fn formats_compact_and_pretty() {
+		let value = vec![vec![1, 2], vec![]];
+		assert_eq!(format!("{}", json_display(&value)), "[[1,2],[]]");
+		assert_eq!(format!("{:#}", json_display(&value)), "[\n\t[\n\t\t1,\n\t\t2\n\t],\n\t[]\n]");
+		assert_eq!(format!("{:#2}", json_display(&value)), "[\n  [\n    1,\n    2\n  ],\n  []\n]");
+		assert_eq!(json_display(&"a\"b").to_string(), "\"a\\\"b\"");
+	}
+
+	#[test]
+	fn writes_non_finite_floats_as_null() {
+		assert_eq!(json_display(&vec![f64::NAN, f64::INFINITY, 1.5]).to_string(), "[null,null,1.5]");
+		assert_eq!(format!("{:#}", json_display(&f32::NEG_INFINITY)), "null");
+	}
+
+	#[test]
+	fn writes_into_fmt_writers() {
+		let mut builder = JSONBuilder::new_fmt(String::from("json: "));
+		builder.value(&[true, false][..]).unwrap();
+		builder.end().unwrap();
+		assert_eq!(builder.into_inner().unwrap().into_inner(), "json: [true,false]");
+	}
+}
@@ -0,0 +1,131 @@
+use core::fmt;
+use alloc::vec::Vec;
+use alloc::string::{String, ToString};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PathSegment {
+	Key(String),
+	Index(usize)
+}
+
+// Location of a value inside of a document, displayed as e.g. `$.users[3].address`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct JSONPath {
+	segments: Vec<PathSegment>
+}
+
+impl JSONPath {
+	pub fn new() -> JSONPath {
+		JSONPath { segments: Vec::new() }
+	}
+
+	pub fn segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	pub fn len(&self) -> usize {
+		self.segments.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.segments.is_empty()
+	}
+
+	pub fn push_key(&mut self, key: &str) {
+		self.segments.push(PathSegment::Key(key.to_string()));
+	}
+
+	pub fn push_index(&mut self, index: usize) {
+		self.segments.push(PathSegment::Index(index));
+	}
+
+	pub fn pop(&mut self) -> Option<PathSegment> {
+		self.segments.pop()
+	}
+
+	// replaces the last key, reusing its allocation
+	pub(crate) fn set_key(&mut self, key: &str) {
+		if let Some(&mut PathSegment::Key(ref mut last)) = self.segments.last_mut() {
+			last.clear();
+			last.push_str(key);
+			return;
+		}
+		self.push_key(key);
+	}
+
+	pub(crate) fn next_index(&mut self) {
+		if let Some(&mut PathSegment::Index(ref mut index)) = self.segments.last_mut() {
+			*index += 1;
+		}
+	}
+
+	pub fn with_key(&self, key: &str) -> JSONPath {
+		let mut path = self.clone();
+		path.push_key(key);
+		path
+	}
+}
+
+// Keys written without quotes, both after a dot in paths and as JSON5 keys.
+// Other digits than 0-9 are not part of identifiers, e.g. a².
+pub(crate) fn is_identifier(key: &str) -> bool {
+	let mut chars = key.chars();
+	match chars.next() {
+		Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {},
+		_ => return false
+	}
+	chars.all(|c| c.is_alphabetic() || c.is_ascii_digit() || c == '_' || c == '$')
+}
+
+impl fmt::Display for JSONPath {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("$")?;
+		for segment in &self.segments {
+			match *segment {
+				PathSegment::Key(ref key) if is_identifier(key) => write!(f, ".{}", key)?,
+				PathSegment::Key(ref key) => write!(f, "[{:?}]", key)?,
+				PathSegment::Index(index) => write!(f, "[{}]", index)?
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn displays_segments() {
+		let mut path = JSONPath::new();
+		assert_eq!(path.to_string(), "$");
+		path.push_key("users");
+		path.push_index(3);
+		path.push_key("$ref_2");
+		assert_eq!(path.to_string(), "$.users[3].$ref_2");
+		path.pop();
+		path.push_key("first name");
+		assert_eq!(path.to_string(), "$.users[3][\"first name\"]");
+		assert_eq!(path.with_key("2nd").to_string(), "$.users[3][\"first name\"][\"2nd\"]");
+		assert_eq!(path.len(), 3);
+	}
+
+	#[test]
+	fn finds_identifiers() {
+		assert!(is_identifier("_schlüssel2"));
+		assert!(is_identifier("ключ"));
+		assert!(!is_identifier("a²"));
+		assert!(!is_identifier("2nd"));
+		assert!(!is_identifier(""));
+	}
+
+	#[test]
+	fn updates_the_last_segment() {
+		let mut path = JSONPath::new();
+		path.push_index(0);
+		path.next_index();
+		path.set_key("a");
+		path.set_key("b");
+		assert_eq!(path.segments(), &[PathSegment::Index(1), PathSegment::Key("b".to_string())][..]);
+	}
+}
@@ -0,0 +1,794 @@
+use std::fmt;
+use std::io::Read;
+use std::vec::Vec;
+
+use crate::json_builder::State;
+use crate::json_path::JSONPath;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Event<'a> {
+	BeginObject,
+	Key(&'a str),
+	BeginArray,
+	String(&'a str),
+	// the raw number literal, so the caller can decide how to parse it
+	Number(&'a str),
+	Bool(bool),
+	Null,
+	EndArray,
+	EndObject
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SyntaxError {
+	UnexpectedChar(char),
+	UnexpectedEOF,
+	InvalidEscape,
+	InvalidUnicode,
+	InvalidNumber,
+	ControlCharacter,
+	TrailingData,
+	TokenTooLong,
+	TooDeep
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+	IO(std::io::Error),
+	Syntax(SyntaxError, usize, usize),
+	// the following carry the path of the offending value
+	Type(&'static str, String),
+	Value(&'static str, String),
+	MissingField(String),
+	UnknownField(String),
+	DuplicateField(String)
+}
+
+impl fmt::Display for SyntaxError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SyntaxError::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+			SyntaxError::UnexpectedEOF => f.write_str("unexpected end of input"),
+			SyntaxError::InvalidEscape => f.write_str("invalid escape sequence"),
+			SyntaxError::InvalidUnicode => f.write_str("invalid unicode"),
+			SyntaxError::InvalidNumber => f.write_str("invalid number"),
+			SyntaxError::ControlCharacter => f.write_str("control character in string"),
+			SyntaxError::TrailingData => f.write_str("trailing data after the document"),
+			SyntaxError::TokenTooLong => f.write_str("string or number is too long"),
+			SyntaxError::TooDeep => f.write_str("nesting is too deep")
+		}
+	}
+}
+
+impl fmt::Display for ReadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReadError::IO(ref err) => write!(f, "I/O error: {}", err),
+			ReadError::Syntax(error, line, column) => write!(f, "{} at line {}, column {}", error, line, column),
+			ReadError::Type(expected, ref path) => write!(f, "expected {} at {}", expected, path),
+			ReadError::Value(message, ref path) => write!(f, "{} at {}", message, path),
+			ReadError::MissingField(ref path) => write!(f, "missing field {}", path),
+			ReadError::UnknownField(ref path) => write!(f, "unknown field {}", path),
+			ReadError::DuplicateField(ref path) => write!(f, "duplicate field {}", path)
+		}
+	}
+}
+
+impl std::error::Error for ReadError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match *self {
+			ReadError::IO(ref err) => Some(err),
+			_ => None
+		}
+	}
+}
+
+pub type ReadResult<T> = std::result::Result<T, ReadError>;
+
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+pub const DEFAULT_MAX_TOKEN_LEN: usize = 1024 * 1024;
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+pub struct JSONReader<R: Read> {
+	stack: Vec<State>,
+	path: JSONPath,
+	peeked: Option<Event<'static>>,
+	reader: R,
+	buffer: Box<[u8]>,
+	pos: usize,
+	len: usize,
+	eof: bool,
+	line: usize,
+	column: usize,
+	scratch: Vec<u8>,
+	max_depth: usize,
+	max_token_len: usize
+}
+
+impl<R: Read> JSONReader<R> {
+	pub fn new(reader: R) -> JSONReader<R> {
+		JSONReader::with_limits(reader, DEFAULT_MAX_DEPTH, DEFAULT_MAX_TOKEN_LEN)
+	}
+
+	// memory use is bounded by the nesting depth and the length of the longest string/number
+	pub fn with_limits(reader: R, max_depth: usize, max_token_len: usize) -> JSONReader<R> {
+		JSONReader {
+			stack: vec![ State::Begin ],
+			path: JSONPath::new(),
+			peeked: None,
+			reader,
+			buffer: vec![0u8; BUFFER_SIZE].into_boxed_slice(),
+			pos: 0,
+			len: 0,
+			eof: false,
+			line: 1,
+			column: 1,
+			scratch: Vec::new(),
+			max_depth,
+			max_token_len
+		}
+	}
+
+	pub fn line(&self) -> usize {
+		self.line
+	}
+
+	pub fn column(&self) -> usize {
+		self.column
+	}
+
+	pub fn state(&self) -> State {
+		*self.stack.last().unwrap()
+	}
+
+	pub fn depth(&self) -> usize {
+		self.stack.len() - 1
+	}
+
+	// path of the most recently read key or value
+	pub fn path(&self) -> &JSONPath {
+		&self.path
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	fn error<T>(&self, error: SyntaxError) -> ReadResult<T> {
+		Err(ReadError::Syntax(error, self.line, self.column))
+	}
+
+	fn peek(&mut self) -> ReadResult<Option<u8>> {
+		if self.pos == self.len {
+			if self.eof {
+				return Ok(None);
+			}
+			loop {
+				match self.reader.read(&mut self.buffer) {
+					Ok(0) => {
+						self.eof = true;
+						return Ok(None);
+					},
+					Ok(len) => {
+						self.pos = 0;
+						self.len = len;
+						break;
+					},
+					Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => {},
+					Err(err) => return Err(ReadError::IO(err))
+				}
+			}
+		}
+		Ok(Some(self.buffer[self.pos]))
+	}
+
+	fn consume(&mut self) {
+		let byte = self.buffer[self.pos];
+		self.pos += 1;
+		if byte == b'\n' {
+			self.line += 1;
+			self.column = 1;
+		} else if byte & 0xC0 != 0x80 {
+			self.column += 1;
+		}
+	}
+
+	fn next_byte(&mut self) -> ReadResult<u8> {
+		match self.peek()? {
+			Some(byte) => {
+				self.consume();
+				Ok(byte)
+			},
+			None => self.error(SyntaxError::UnexpectedEOF)
+		}
+	}
+
+	fn unexpected<T>(&mut self, byte: Option<u8>) -> ReadResult<T> {
+		match byte {
+			None => self.error(SyntaxError::UnexpectedEOF),
+			Some(byte) if byte < 0x80 => self.error(SyntaxError::UnexpectedChar(byte as char)),
+			Some(_) => {
+				// decode the whole char for a meaningful error message
+				let mut bytes = [0u8; 4];
+				let mut len = 0;
+				while len < 4 {
+					match self.peek()? {
+						Some(byte) if len == 0 || byte & 0xC0 == 0x80 => {
+							bytes[len] = byte;
+							self.pos += 1;
+							len += 1;
+						},
+						_ => break
+					}
+				}
+				match std::str::from_utf8(&bytes[..len]).ok().and_then(|s| s.chars().next()) {
+					Some(c) => self.error(SyntaxError::UnexpectedChar(c)),
+					None => self.error(SyntaxError::InvalidUnicode)
+				}
+			}
+		}
+	}
+
+	fn skip_whitespace(&mut self) -> ReadResult<Option<u8>> {
+		loop {
+			match self.peek()? {
+				Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') => self.consume(),
+				other => return Ok(other)
+			}
+		}
+	}
+
+	fn push_scratch(&mut self, byte: u8) -> ReadResult<()> {
+		if self.scratch.len() >= self.max_token_len {
+			return self.error(SyntaxError::TokenTooLong);
+		}
+		self.scratch.push(byte);
+		Ok(())
+	}
+
+	fn push_state(&mut self, state: State) -> ReadResult<()> {
+		if self.stack.len() > self.max_depth {
+			return self.error(SyntaxError::TooDeep);
+		}
+		self.stack.push(state);
+		Ok(())
+	}
+
+	fn after_value(&mut self) {
+		let i = self.stack.len() - 1;
+		match self.stack[i] {
+			State::ArrayFirstElement => {
+				self.stack[i] = State::ArrayElement;
+			},
+
+			State::ObjectValue => {
+				self.stack[i] = State::ObjectKey;
+			},
+
+			State::Begin => {
+				self.stack[i] = State::End;
+			},
+
+			_ => {}
+		}
+	}
+
+	fn expect_literal(&mut self, literal: &[u8]) -> ReadResult<()> {
+		for &expected in literal {
+			let byte = self.peek()?;
+			if byte != Some(expected) {
+				return self.unexpected(byte);
+			}
+			self.consume();
+		}
+		Ok(())
+	}
+
+	fn read_hex4(&mut self) -> ReadResult<u32> {
+		let mut value = 0u32;
+		for _ in 0..4 {
+			let digit = match self.peek()? {
+				Some(byte @ b'0' ..= b'9') => byte - b'0',
+				Some(byte @ b'a' ..= b'f') => byte - b'a' + 10,
+				Some(byte @ b'A' ..= b'F') => byte - b'A' + 10,
+				_ => return self.error(SyntaxError::InvalidEscape)
+			};
+			self.consume();
+			value = (value << 4) | digit as u32;
+		}
+		Ok(value)
+	}
+
+	fn read_unicode_escape(&mut self) -> ReadResult<char> {
+		let high = self.read_hex4()?;
+		let code = if (0xD800..0xDC00).contains(&high) {
+			if self.next_byte()? != b'\\' || self.next_byte()? != b'u' {
+				return self.error(SyntaxError::InvalidUnicode);
+			}
+			let low = self.read_hex4()?;
+			if !(0xDC00..0xE000).contains(&low) {
+				return self.error(SyntaxError::InvalidUnicode);
+			}
+			0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+		} else {
+			high
+		};
+
+		match std::char::from_u32(code) {
+			Some(c) => Ok(c),
+			None => self.error(SyntaxError::InvalidUnicode)
+		}
+	}
+
+	// reads a string literal into the scratch buffer, the opening quote is already consumed
+	fn read_string(&mut self) -> ReadResult<()> {
+		self.scratch.clear();
+		loop {
+			let byte = match self.peek()? {
+				Some(0x00 ..= 0x1F) => return self.error(SyntaxError::ControlCharacter),
+				Some(byte) => byte,
+				None => return self.error(SyntaxError::UnexpectedEOF)
+			};
+			self.consume();
+			match byte {
+				b'"' => break,
+
+				b'\\' => {
+					let escape = match self.peek()? {
+						Some(escape) => escape,
+						None => return self.error(SyntaxError::UnexpectedEOF)
+					};
+					let unescaped = match escape {
+						b'"'  => b'"',
+						b'\\' => b'\\',
+						b'/'  => b'/',
+						b'b'  => 0x08,
+						b'f'  => 0x0C,
+						b'n'  => b'\n',
+						b'r'  => b'\r',
+						b't'  => b'\t',
+						b'u'  => {
+							self.consume();
+							let c = self.read_unicode_escape()?;
+							let mut bytes = [0u8; 4];
+							for &byte in c.encode_utf8(&mut bytes).as_bytes() {
+								self.push_scratch(byte)?;
+							}
+							continue;
+						},
+						_ => return self.error(SyntaxError::InvalidEscape)
+					};
+					self.consume();
+					self.push_scratch(unescaped)?;
+				},
+
+				_ => self.push_scratch(byte)?
+			}
+		}
+
+		if std::str::from_utf8(&self.scratch).is_err() {
+			return self.error(SyntaxError::InvalidUnicode);
+		}
+
+		Ok(())
+	}
+
+	fn read_digits(&mut self) -> ReadResult<usize> {
+		let mut count = 0;
+		while let Some(byte @ b'0' ..= b'9') = self.peek()? {
+			self.consume();
+			self.push_scratch(byte)?;
+			count += 1;
+		}
+		Ok(count)
+	}
+
+	fn read_number(&mut self) -> ReadResult<()> {
+		self.scratch.clear();
+
+		if self.peek()? == Some(b'-') {
+			self.consume();
+			self.push_scratch(b'-')?;
+		}
+
+		match self.peek()? {
+			Some(b'0') => {
+				self.consume();
+				self.push_scratch(b'0')?;
+			},
+			Some(b'1' ..= b'9') => {
+				self.read_digits()?;
+			},
+			_ => return self.error(SyntaxError::InvalidNumber)
+		}
+
+		if self.peek()? == Some(b'.') {
+			self.consume();
+			self.push_scratch(b'.')?;
+			if self.read_digits()? == 0 {
+				return self.error(SyntaxError::InvalidNumber);
+			}
+		}
+
+		if let Some(byte @ b'e') | Some(byte @ b'E') = self.peek()? {
+			self.consume();
+			self.push_scratch(byte)?;
+			if let Some(sign @ b'+') | Some(sign @ b'-') = self.peek()? {
+				self.consume();
+				self.push_scratch(sign)?;
+			}
+			if self.read_digits()? == 0 {
+				return self.error(SyntaxError::InvalidNumber);
+			}
+		}
+
+		Ok(())
+	}
+
+	fn scratch_str(&self) -> &str {
+		// the scratch buffer is validated by read_string() or only contains ASCII
+		std::str::from_utf8(&self.scratch).unwrap()
+	}
+
+	fn read_value(&mut self) -> ReadResult<Event<'_>> {
+		let byte = self.skip_whitespace()?;
+		match byte {
+			Some(b'{') => {
+				self.consume();
+				self.push_state(State::ObjectFirstKey)?;
+				Ok(Event::BeginObject)
+			},
+
+			Some(b'[') => {
+				self.consume();
+				self.push_state(State::ArrayFirstElement)?;
+				Ok(Event::BeginArray)
+			},
+
+			Some(b'"') => {
+				self.consume();
+				self.read_string()?;
+				self.after_value();
+				Ok(Event::String(self.scratch_str()))
+			},
+
+			Some(b't') => {
+				self.expect_literal(b"true")?;
+				self.after_value();
+				Ok(Event::Bool(true))
+			},
+
+			Some(b'f') => {
+				self.expect_literal(b"false")?;
+				self.after_value();
+				Ok(Event::Bool(false))
+			},
+
+			Some(b'n') => {
+				self.expect_literal(b"null")?;
+				self.after_value();
+				Ok(Event::Null)
+			},
+
+			Some(b'-') | Some(b'0' ..= b'9') => {
+				self.read_number()?;
+				self.after_value();
+				Ok(Event::Number(self.scratch_str()))
+			},
+
+			_ => self.unexpected(byte)
+		}
+	}
+
+	fn read_key(&mut self) -> ReadResult<Event<'_>> {
+		let byte = self.skip_whitespace()?;
+		if byte != Some(b'"') {
+			return self.unexpected(byte);
+		}
+		self.consume();
+		self.read_string()?;
+
+		let byte = self.skip_whitespace()?;
+		if byte != Some(b':') {
+			return self.unexpected(byte);
+		}
+		self.consume();
+
+		let i = self.stack.len() - 1;
+		if self.stack[i] == State::ObjectFirstKey {
+			self.path.push_key("");
+		}
+		self.stack[i] = State::ObjectValue;
+		self.path.set_key(std::str::from_utf8(&self.scratch).unwrap());
+		Ok(Event::Key(self.scratch_str()))
+	}
+
+	fn end_container(&mut self, event: Event<'static>) -> ReadResult<Event<'static>> {
+		self.consume();
+		match self.stack.pop() {
+			Some(State::ArrayElement) | Some(State::ObjectKey) => {
+				self.path.pop();
+			},
+			_ => {}
+		}
+		self.after_value();
+		Ok(event)
+	}
+
+	// Returns the next event or None once the document is complete.
+	pub fn next_event(&mut self) -> ReadResult<Option<Event<'_>>> {
+		if let Some(event) = self.peeked.take() {
+			return Ok(Some(self.attach(event)));
+		}
+		self.read_event()
+	}
+
+	// Returns the next event without consuming it.
+	pub fn peek_event(&mut self) -> ReadResult<Option<Event<'_>>> {
+		if self.peeked.is_none() {
+			self.peeked = match self.read_event()? {
+				Some(event) => Some(detach(event)),
+				None => return Ok(None)
+			};
+		}
+		match self.peeked {
+			Some(event) => Ok(Some(self.attach(event))),
+			None => Ok(None)
+		}
+	}
+
+	fn attach(&self, event: Event<'static>) -> Event<'_> {
+		match event {
+			Event::Key(_)    => Event::Key(self.scratch_str()),
+			Event::String(_) => Event::String(self.scratch_str()),
+			Event::Number(_) => Event::Number(self.scratch_str()),
+			event => event
+		}
+	}
+
+	fn read_event(&mut self) -> ReadResult<Option<Event<'_>>> {
+		let byte = self.skip_whitespace()?;
+		match self.state() {
+			State::Begin | State::ObjectValue =>
+				self.read_value().map(Some),
+
+			State::End => match byte {
+				None => Ok(None),
+				Some(_) => self.error(SyntaxError::TrailingData)
+			},
+
+			State::ArrayFirstElement => match byte {
+				Some(b']') => self.end_container(Event::EndArray).map(Some),
+				_ => {
+					self.path.push_index(0);
+					self.read_value().map(Some)
+				}
+			},
+
+			State::ArrayElement => match byte {
+				Some(b']') => self.end_container(Event::EndArray).map(Some),
+				Some(b',') => {
+					self.consume();
+					self.path.next_index();
+					self.read_value().map(Some)
+				},
+				_ => self.unexpected(byte)
+			},
+
+			State::ObjectFirstKey => match byte {
+				Some(b'}') => self.end_container(Event::EndObject).map(Some),
+				_ => self.read_key().map(Some)
+			},
+
+			State::ObjectKey => match byte {
+				Some(b'}') => self.end_container(Event::EndObject).map(Some),
+				Some(b',') => {
+					self.consume();
+					self.read_key().map(Some)
+				},
+				_ => self.unexpected(byte)
+			}
+		}
+	}
+
+	// Reads the end of the document, failing if there is any trailing data.
+	pub fn end(&mut self) -> ReadResult<()> {
+		match self.next_event()? {
+			None => Ok(()),
+			Some(_) => self.error(SyntaxError::TrailingData)
+		}
+	}
+
+	pub fn begin_object(&mut self) -> ReadResult<()> {
+		match self.next_event()? {
+			Some(Event::BeginObject) => Ok(()),
+			_ => self.type_error("an object")
+		}
+	}
+
+	// Returns the next key of the current object or None at its end.
+	pub fn next_key(&mut self) -> ReadResult<Option<String>> {
+		match self.next_event()? {
+			Some(Event::Key(key)) => Ok(Some(key.to_string())),
+			Some(Event::EndObject) => Ok(None),
+			_ => self.type_error("an object key")
+		}
+	}
+
+	pub fn begin_array(&mut self) -> ReadResult<()> {
+		match self.next_event()? {
+			Some(Event::BeginArray) => Ok(()),
+			_ => self.type_error("an array")
+		}
+	}
+
+	// Returns true if the current array has another element, consumes the end of the array otherwise.
+	pub fn next_element(&mut self) -> ReadResult<bool> {
+		match self.peek_event()? {
+			Some(Event::EndArray) => {
+				self.peeked = None;
+				Ok(false)
+			},
+			_ => Ok(true)
+		}
+	}
+
+	// Fails without consuming anything at the end of an array or object.
+	pub fn skip_value(&mut self) -> ReadResult<()> {
+		if let Some(Event::EndObject) | Some(Event::EndArray) = self.peek_event()? {
+			return self.type_error("a value");
+		}
+		let mut depth = 0usize;
+		loop {
+			match self.next_event()? {
+				Some(Event::BeginObject) | Some(Event::BeginArray) => depth += 1,
+				Some(Event::EndObject) | Some(Event::EndArray) => depth -= 1,
+				Some(Event::Key(_)) => continue,
+				Some(_) => {},
+				None => return self.error(SyntaxError::UnexpectedEOF)
+			}
+			if depth == 0 {
+				return Ok(());
+			}
+		}
+	}
+
+	pub fn type_error<T>(&self, expected: &'static str) -> ReadResult<T> {
+		Err(ReadError::Type(expected, self.path.to_string()))
+	}
+
+	pub fn value_error<T>(&self, message: &'static str) -> ReadResult<T> {
+		Err(ReadError::Value(message, self.path.to_string()))
+	}
+
+	pub fn missing_field<T>(&self, key: &str) -> ReadResult<T> {
+		Err(ReadError::MissingField(self.path.with_key(key).to_string()))
+	}
+
+	pub fn unknown_field<T>(&self) -> ReadResult<T> {
+		Err(ReadError::UnknownField(self.path.to_string()))
+	}
+
+	pub fn duplicate_field<T>(&self) -> ReadResult<T> {
+		Err(ReadError::DuplicateField(self.path.to_string()))
+	}
+}
+
+fn detach(event: Event) -> Event<'static> {
+	match event {
+		Event::Key(_)     => Event::Key(""),
+		Event::String(_)  => Event::String(""),
+		Event::Number(_)  => Event::Number(""),
+		Event::BeginObject => Event::BeginObject,
+		Event::BeginArray  => Event::BeginArray,
+		Event::Bool(value) => Event::Bool(value),
+		Event::Null        => Event::Null,
+		Event::EndArray    => Event::EndArray,
+		Event::EndObject   => Event::EndObject
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn events(json: &str) -> ReadResult<Vec<String>> {
+		let mut reader = JSONReader::new(json.as_bytes());
+		let mut events = Vec::new();
+		while let Some(event) = reader.next_event()? {
+			events.push(format!("{:?}", event));
+		}
+		Ok(events)
+	}
+
+	fn syntax_error(json: &str) -> (SyntaxError, usize, usize) {
+		let mut reader = JSONReader::with_limits(json.as_bytes(), 4, 8);
+		loop {
+			match reader.next_event() {
+				Err(ReadError::Syntax(error, line, column)) => return (error, line, column),
+				Err(err) => panic!("unexpected error: {:?}", err),
+				Ok(None) => panic!("no error in {:?}", json),
+				Ok(Some(_)) => {}
+			}
+		}
+	}
+
+	#[test]
+	fn reads_events() {
+		assert_eq!(events(" {\"a\": [1, -2.5e3, \"x\\u00e9\\n\"], \"b\": {}, \"c\": [true, false, null]} ").unwrap(), vec![
+			"BeginObject", "Key(\"a\")", "BeginArray", "Number(\"1\")", "Number(\"-2.5e3\")",
+			"String(\"xé\\n\")", "EndArray", "Key(\"b\")", "BeginObject", "EndObject", "Key(\"c\")",
+			"BeginArray", "Bool(true)", "Bool(false)", "Null", "EndArray", "EndObject"
+		]);
+		assert_eq!(events("\"\\ud83d\\ude00\"").unwrap(), vec!["String(\"😀\")"]);
+	}
+
+	#[test]
+	fn reports_error_positions() {
+		assert_eq!(syntax_error("{\n  \"a\": tru }"), (SyntaxError::UnexpectedChar(' '), 2, 11));
+		assert_eq!(syntax_error("[1,\n 2,]"), (SyntaxError::UnexpectedChar(']'), 2, 4));
+		assert_eq!(syntax_error("[\"ä\" 1]"), (SyntaxError::UnexpectedChar('1'), 1, 6));
+		assert_eq!(syntax_error("[01]"), (SyntaxError::UnexpectedChar('1'), 1, 3));
+		assert_eq!(syntax_error("[1.]"), (SyntaxError::InvalidNumber, 1, 4));
+		assert_eq!(syntax_error("\"a\\x\""), (SyntaxError::InvalidEscape, 1, 4));
+		assert_eq!(syntax_error("\"\\ud800\""), (SyntaxError::InvalidUnicode, 1, 9));
+		assert_eq!(syntax_error("\"a\tb\""), (SyntaxError::ControlCharacter, 1, 3));
+		assert_eq!(syntax_error("{\"a\""), (SyntaxError::UnexpectedEOF, 1, 5));
+		assert_eq!(syntax_error("1 2"), (SyntaxError::TrailingData, 1, 3));
+	}
+
+	#[test]
+	fn enforces_limits() {
+		assert_eq!(syntax_error("[[[[[]]]]]"), (SyntaxError::TooDeep, 1, 6));
+		assert!(events("[[[[]]]]").is_ok());
+		assert_eq!(syntax_error("\"123456789\""), (SyntaxError::TokenTooLong, 1, 11));
+		assert_eq!(syntax_error("123456789"), (SyntaxError::TokenTooLong, 1, 10));
+	}
+
+	#[test]
+	fn tracks_path() {
+		let mut reader = JSONReader::new("{\"users\": [{\"name\": 1}]}".as_bytes());
+		for _ in 0..5 {
+			reader.next_event().unwrap();
+		}
+		assert_eq!(reader.path().to_string(), "$.users[0].name");
+		match reader.begin_array() {
+			Err(ReadError::Type("an array", path)) => assert_eq!(path, "$.users[0].name"),
+			other => panic!("unexpected result: {:?}", other)
+		}
+	}
+
+	#[test]
+	fn skips_values() {
+		let mut reader = JSONReader::new("[{\"a\": [1, {}]}, 2]".as_bytes());
+		reader.begin_array().unwrap();
+		reader.skip_value().unwrap();
+		assert_eq!(reader.next_event().unwrap(), Some(Event::Number("2")));
+		match reader.skip_value() {
+			Err(ReadError::Type("a value", _)) => {},
+			other => panic!("unexpected result: {:?}", other)
+		}
+		assert!(!reader.next_element().unwrap());
+
+		let mut reader = JSONReader::new("{}".as_bytes());
+		reader.next_event().unwrap();
+		assert!(reader.skip_value().is_err());
+		assert_eq!(reader.next_event().unwrap(), Some(Event::EndObject));
+	}
+
+	#[test]
+	fn displays_errors() {
+		let error = JSONReader::new("[1,]".as_bytes()).skip_value().unwrap_err();
+		assert_eq!(error.to_string(), "unexpected character ']' at line 1, column 4");
+		let mut reader = JSONReader::new("{\"a\": true}".as_bytes());
+		reader.next_event().unwrap();
+		reader.next_event().unwrap();
+		assert_eq!(reader.begin_array().unwrap_err().to_string(), "expected an array at $.a");
+		assert_eq!(reader.missing_field::<()>("b").unwrap_err().to_string(), "missing field $.a.b");
+
+		let error = ReadError::IO(std::io::Error::other("gone"));
+		assert_eq!(error.to_string(), "I/O error: gone");
+		assert_eq!(std::error::Error::source(&error).unwrap().to_string(), "gone");
+	}
+}
@@ -0,0 +1,701 @@
+use alloc::vec::Vec;
+use alloc::string::{String, ToString};
+use alloc::boxed::Box;
+use core::ops::{Index, IndexMut};
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use crate::json_builder::{IntoJSON, JSONBuilder, Result};
+use crate::sink::Sink;
+#[cfg(feature = "std")]
+use crate::json_reader::{JSONReader, ReadResult, Event};
+#[cfg(feature = "std")]
+use crate::from_json::FromJSON;
+
+// Numbers compare by value, so Int(1), UInt(1) and Float(1.0) are equal.
+#[derive(Copy, Clone, Debug)]
+pub enum Number {
+	Int(i128),
+	UInt(u128),
+	Float(f64)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(Number),
+	String(String),
+	Array(Vec<Value>),
+	Object(Map)
+}
+
+// Object members in insertion order. Lookups are linear, which is fine for
+// the size of typical objects.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Map {
+	entries: Vec<(String, Value)>
+}
+
+static NULL: Value = Value::Null;
+
+// Collects what a builder writes as a Value, see IntoJSON::to_value().
+pub(crate) struct ValueTree {
+	// the open arrays and objects, each with the key of its next member
+	open: Vec<(Value, Option<String>)>,
+	root: Option<Value>
+}
+
+impl ValueTree {
+	pub(crate) fn new() -> ValueTree {
+		ValueTree { open: Vec::new(), root: None }
+	}
+
+	pub(crate) fn begin(&mut self, container: Value) {
+		self.open.push((container, None));
+	}
+
+	pub(crate) fn key(&mut self, key: &str) {
+		if let Some(last) = self.open.last_mut() {
+			last.1 = Some(key.to_string());
+		}
+	}
+
+	pub(crate) fn value(&mut self, value: Value) {
+		match self.open.last_mut() {
+			Some(&mut (Value::Array(ref mut array), _)) => array.push(value),
+			Some(&mut (Value::Object(ref mut object), ref mut key)) => {
+				if let Some(key) = key.take() {
+					object.insert(key, value);
+				}
+			},
+			_ => self.root = Some(value)
+		}
+	}
+
+	pub(crate) fn end(&mut self) {
+		if let Some((container, _)) = self.open.pop() {
+			self.value(container);
+		}
+	}
+
+	pub(crate) fn reset(&mut self) {
+		self.open.clear();
+		self.root = None;
+	}
+
+	pub(crate) fn take(&mut self) -> Option<Value> {
+		self.root.take()
+	}
+}
+
+// The sink of builders that only collect values.
+pub(crate) struct Discard;
+
+impl Sink for Discard {
+	fn write_bytes(&mut self, _bytes: &[u8]) -> Result {
+		Ok(())
+	}
+}
+
+impl Number {
+	pub fn as_i64(&self) -> Option<i64> {
+		match *self {
+			Number::Int(value)  => if value >= i64::MIN as i128 && value <= i64::MAX as i128 { Some(value as i64) } else { None },
+			Number::UInt(value) => if value <= i64::MAX as u128 { Some(value as i64) } else { None },
+			Number::Float(_)    => None
+		}
+	}
+
+	pub fn as_u64(&self) -> Option<u64> {
+		match *self {
+			Number::Int(value)  => if value >= 0 && value <= u64::MAX as i128 { Some(value as u64) } else { None },
+			Number::UInt(value) => if value <= u64::MAX as u128 { Some(value as u64) } else { None },
+			Number::Float(_)    => None
+		}
+	}
+
+	pub fn as_f64(&self) -> f64 {
+		match *self {
+			Number::Int(value)   => value as f64,
+			Number::UInt(value)  => value as f64,
+			Number::Float(value) => value
+		}
+	}
+
+	#[cfg(feature = "std")]
+	fn parse(number: &str) -> Option<Number> {
+		if let Ok(value) = number.parse::<u128>() {
+			Some(Number::UInt(value))
+		} else if let Ok(value) = number.parse::<i128>() {
+			Some(Number::Int(value))
+		} else {
+			// e.g. 1e400, which Value couldn't write back
+			number.parse::<f64>().ok().filter(|value| value.is_finite()).map(Number::Float)
+		}
+	}
+}
+
+impl PartialEq for Number {
+	fn eq(&self, other: &Number) -> bool {
+		match (*self, *other) {
+			(Number::Int(a), Number::Int(b)) => a == b,
+			(Number::UInt(a), Number::UInt(b)) => a == b,
+			(Number::Float(a), Number::Float(b)) => a == b,
+			(Number::Int(a), Number::UInt(b)) | (Number::UInt(b), Number::Int(a)) =>
+				a >= 0 && a as u128 == b,
+			// only integral floats within range can be equal, the casts are exact for those
+			(Number::Float(a), Number::Int(b)) | (Number::Int(b), Number::Float(a)) =>
+				(-I128_RANGE..I128_RANGE).contains(&a) && a as i128 as f64 == a && a as i128 == b,
+			(Number::Float(a), Number::UInt(b)) | (Number::UInt(b), Number::Float(a)) =>
+				(0.0..U128_RANGE).contains(&a) && a as u128 as f64 == a && a as u128 == b
+		}
+	}
+}
+
+// 2^127 and 2^128, the MAX values round up to them
+const I128_RANGE: f64 = i128::MAX as f64;
+const U128_RANGE: f64 = u128::MAX as f64;
+
+impl Map {
+	pub fn new() -> Map {
+		Map { entries: Vec::new() }
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		self.entries.iter().find(|entry| entry.0 == key).map(|entry| &entry.1)
+	}
+
+	pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+		self.entries.iter_mut().find(|entry| entry.0 == key).map(|entry| &mut entry.1)
+	}
+
+	pub fn contains_key(&self, key: &str) -> bool {
+		self.get(key).is_some()
+	}
+
+	// Replaces the value of an existing key in place, so the order is kept stable.
+	pub fn insert<K: Into<String>, V: Into<Value>>(&mut self, key: K, value: V) -> Option<Value> {
+		let key = key.into();
+		let value = value.into();
+		if let Some(old) = self.get_mut(&key) {
+			return Some(core::mem::replace(old, value));
+		}
+		self.entries.push((key, value));
+		None
+	}
+
+	pub fn remove(&mut self, key: &str) -> Option<Value> {
+		match self.entries.iter().position(|entry| entry.0 == key) {
+			Some(index) => Some(self.entries.remove(index).1),
+			None => None
+		}
+	}
+
+	pub fn iter(&self) -> core::slice::Iter<'_, (String, Value)> {
+		self.entries.iter()
+	}
+
+	pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, (String, Value)> {
+		self.entries.iter_mut()
+	}
+
+	pub fn keys(&self) -> impl Iterator<Item = &str> {
+		self.entries.iter().map(|entry| entry.0.as_str())
+	}
+}
+
+impl<'a> IntoIterator for &'a Map {
+	type Item = &'a (String, Value);
+	type IntoIter = core::slice::Iter<'a, (String, Value)>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.entries.iter()
+	}
+}
+
+impl IntoIterator for Map {
+	type Item = (String, Value);
+	type IntoIter = alloc::vec::IntoIter<(String, Value)>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.entries.into_iter()
+	}
+}
+
+impl Value {
+	pub fn get<I: ValueIndex>(&self, index: I) -> Option<&Value> {
+		index.index_into(self)
+	}
+
+	pub fn get_mut<I: ValueIndex>(&mut self, index: I) -> Option<&mut Value> {
+		index.index_into_mut(self)
+	}
+
+	pub fn is_null(&self) -> bool {
+		*self == Value::Null
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			Value::Bool(value) => Some(value),
+			_ => None
+		}
+	}
+
+	pub fn as_number(&self) -> Option<Number> {
+		match *self {
+			Value::Number(number) => Some(number),
+			_ => None
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Value::String(ref value) => Some(value),
+			_ => None
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&Vec<Value>> {
+		match *self {
+			Value::Array(ref array) => Some(array),
+			_ => None
+		}
+	}
+
+	pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+		match *self {
+			Value::Array(ref mut array) => Some(array),
+			_ => None
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&Map> {
+		match *self {
+			Value::Object(ref object) => Some(object),
+			_ => None
+		}
+	}
+
+	pub fn as_object_mut(&mut self) -> Option<&mut Map> {
+		match *self {
+			Value::Object(ref mut object) => Some(object),
+			_ => None
+		}
+	}
+}
+
+pub trait ValueIndex {
+	fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value>;
+	fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value>;
+}
+
+impl ValueIndex for usize {
+	fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+		match *value {
+			Value::Array(ref array) => array.get(*self),
+			_ => None
+		}
+	}
+
+	fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+		match *value {
+			Value::Array(ref mut array) => array.get_mut(*self),
+			_ => None
+		}
+	}
+}
+
+impl ValueIndex for str {
+	fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+		match *value {
+			Value::Object(ref object) => object.get(self),
+			_ => None
+		}
+	}
+
+	fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+		match *value {
+			Value::Object(ref mut object) => object.get_mut(self),
+			_ => None
+		}
+	}
+}
+
+impl ValueIndex for String {
+	fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+		self.as_str().index_into(value)
+	}
+
+	fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+		self.as_str().index_into_mut(value)
+	}
+}
+
+impl<T: ValueIndex + ?Sized> ValueIndex for &T {
+	fn index_into<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+		(**self).index_into(value)
+	}
+
+	fn index_into_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+		(**self).index_into_mut(value)
+	}
+}
+
+// Missing keys and indices yield Null, like in JavaScript.
+impl<I: ValueIndex> Index<I> for Value {
+	type Output = Value;
+
+	fn index(&self, index: I) -> &Value {
+		index.index_into(self).unwrap_or(&NULL)
+	}
+}
+
+impl<'a> IndexMut<&'a str> for Value {
+	// Inserts Null for missing keys. Panics if the value is not an object.
+	fn index_mut(&mut self, key: &'a str) -> &mut Value {
+		match *self {
+			Value::Object(ref mut object) => {
+				if !object.contains_key(key) {
+					object.insert(key, Value::Null);
+				}
+				object.get_mut(key).unwrap()
+			},
+			_ => panic!("cannot index into a non-object value with key {:?}", key)
+		}
+	}
+}
+
+impl IndexMut<usize> for Value {
+	// Panics if the value is not an array or the index is out of bounds.
+	fn index_mut(&mut self, index: usize) -> &mut Value {
+		match *self {
+			Value::Array(ref mut array) => {
+				let len = array.len();
+				match array.get_mut(index) {
+					Some(value) => value,
+					None => panic!("index {} out of bounds for array of length {}", index, len)
+				}
+			},
+			_ => panic!("cannot index into a non-array value with index {}", index)
+		}
+	}
+}
+
+impl IntoJSON for Number {
+	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		match *self {
+			Number::Int(value)   => value.write_json(builder),
+			Number::UInt(value)  => value.write_json(builder),
+			Number::Float(value) => value.write_json(builder)
+		}
+	}
+}
+
+impl IntoJSON for Map {
+	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		builder.begin_object()?;
+		for (key, value) in &self.entries {
+			builder.item(key, value)?;
+		}
+		builder.end_object()
+	}
+}
+
+impl IntoJSON for Value {
+	fn into_json(&self, builder: &mut JSONBuilder) -> Result {
+		self.write_json(builder)
+	}
+
+	fn write_json<W: Sink>(&self, builder: &mut JSONBuilder<W>) -> Result {
+		match *self {
+			Value::Null => builder.null(),
+			Value::Bool(value) => value.write_json(builder),
+			Value::Number(ref number) => number.write_json(builder),
+			Value::String(ref value) => value.write_json(builder),
+			Value::Array(ref array) => array.write_json(builder),
+			Value::Object(ref object) => object.write_json(builder)
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl FromJSON for Value {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		let number = match reader.next_event()? {
+			Some(Event::Null) => return Ok(Value::Null),
+			Some(Event::Bool(value)) => return Ok(Value::Bool(value)),
+			Some(Event::String(value)) => return Ok(Value::String(value.to_string())),
+			Some(Event::Number(number)) => Number::parse(number),
+			Some(Event::BeginArray) => {
+				let mut array = Vec::new();
+				while reader.next_element()? {
+					array.push(Value::from_json(reader)?);
+				}
+				return Ok(Value::Array(array));
+			},
+			Some(Event::BeginObject) => {
+				let mut object = Map::new();
+				while let Some(key) = reader.next_key()? {
+					if object.contains_key(&key) {
+						return reader.duplicate_field();
+					}
+					let value = Value::from_json(reader)?;
+					object.entries.push((key, value));
+				}
+				return Ok(Value::Object(object));
+			},
+			_ => return reader.type_error("a value")
+		};
+
+		match number {
+			Some(number) => Ok(Value::Number(number)),
+			None => reader.value_error("number out of range")
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl FromJSON for Map {
+	fn from_json<R: Read>(reader: &mut JSONReader<R>) -> ReadResult<Self> {
+		match reader.peek_event()? {
+			Some(Event::BeginObject) => {},
+			_ => return reader.type_error("an object")
+		}
+		match Value::from_json(reader)? {
+			Value::Object(object) => Ok(object),
+			_ => unreachable!()
+		}
+	}
+}
+
+impl From<Number> for Value {
+	fn from(number: Number) -> Value {
+		Value::Number(number)
+	}
+}
+
+impl From<Map> for Value {
+	fn from(object: Map) -> Value {
+		Value::Object(object)
+	}
+}
+
+impl From<bool> for Value {
+	fn from(value: bool) -> Value {
+		Value::Bool(value)
+	}
+}
+
+macro_rules! impl_from_for_value {
+	($variant:ident as $as:ty: $($t:ty),+) => {
+		$(#[allow(clippy::unnecessary_cast)]
+		impl From<$t> for Value {
+			fn from(value: $t) -> Value {
+				Value::Number(Number::$variant(value as $as))
+			}
+		})+
+	}
+}
+
+impl_from_for_value!{ Int as i128: i8, i16, i32, i64, i128, isize }
+impl_from_for_value!{ UInt as u128: u8, u16, u32, u64, u128, usize }
+impl_from_for_value!{ Float as f64: f64 }
+
+impl From<f32> for Value {
+	// Widen through the shortest decimal representation, so 0.1f32 stays 0.1
+	// instead of becoming 0.10000000149011612.
+	fn from(value: f32) -> Value {
+		let widened = match value.to_string().parse::<f64>() {
+			Ok(widened) => widened,
+			Err(_) => value as f64
+		};
+		Value::Number(Number::Float(widened))
+	}
+}
+
+impl From<String> for Value {
+	fn from(value: String) -> Value {
+		Value::String(value)
+	}
+}
+
+impl<'a> From<&'a str> for Value {
+	fn from(value: &'a str) -> Value {
+		Value::String(value.to_string())
+	}
+}
+
+impl From<char> for Value {
+	fn from(value: char) -> Value {
+		Value::String(value.to_string())
+	}
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+	fn from(value: Option<T>) -> Value {
+		match value {
+			Some(value) => value.into(),
+			None => Value::Null
+		}
+	}
+}
+
+impl<T: Into<Value>> From<Box<T>> for Value {
+	fn from(value: Box<T>) -> Value {
+		(*value).into()
+	}
+}
+
+impl<'a, T: Clone + Into<Value>> From<&'a [T]> for Value {
+	fn from(value: &'a [T]) -> Value {
+		Value::Array(value.iter().cloned().map(Into::into).collect())
+	}
+}
+
+macro_rules! impl_from_map_for_value {
+	($($t:ty),+) => {
+		$(
+			impl<'a, T: Into<Value>> From<$t> for Value {
+				fn from(map: $t) -> Value {
+					let mut object = Map::new();
+					for (key, value) in map {
+						object.entries.push((key.to_string(), value.into()));
+					}
+					Value::Object(object)
+				}
+			}
+		)+
+	}
+}
+
+impl_from_map_for_value!{
+	alloc::collections::BTreeMap<String, T>,
+	alloc::collections::BTreeMap<&'a str, T>
+}
+
+#[cfg(feature = "std")]
+impl_from_map_for_value!{
+	std::collections::HashMap<String, T>,
+	std::collections::HashMap<&'a str, T>
+}
+
+macro_rules! impl_from_iterable_for_value {
+	($($t:ty),+) => {
+		$(
+			impl<T: Into<Value>> From<$t> for Value {
+				fn from(items: $t) -> Value {
+					Value::Array(items.into_iter().map(Into::into).collect())
+				}
+			}
+		)+
+	}
+}
+
+impl_from_iterable_for_value!{
+	Vec<T>,
+	alloc::collections::BTreeSet<T>,
+	alloc::collections::LinkedList<T>,
+	alloc::collections::VecDeque<T>
+}
+
+macro_rules! impl_from_array_for_value {
+	($($n:expr),+) => {
+		$(
+			impl<T: Into<Value>> From<[T; $n]> for Value {
+				fn from(items: [T; $n]) -> Value {
+					Value::Array(IntoIterator::into_iter(items).map(Into::into).collect())
+				}
+			}
+		)+
+	}
+}
+
+impl_from_array_for_value!{
+	0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+	17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec;
+
+	#[test]
+	fn indexes_and_modifies() {
+		let mut object = Map::new();
+		object.insert("b", 1);
+		object.insert("a", vec![true, false]);
+		let mut value = Value::from(object);
+
+		assert_eq!(value["b"], Value::from(1));
+		assert_eq!(value["a"][1], Value::Bool(false));
+		assert!(value["missing"][3].is_null());
+
+		value["c"] = Value::from("new");
+		*value.get_mut("b").unwrap() = Value::Null;
+		value["a"][0] = Value::from(2.5);
+		assert_eq!(value.as_object().unwrap().keys().collect::<Vec<_>>(), vec!["b", "a", "c"]);
+		assert_eq!(value.to_json().unwrap(), "{\"b\":null,\"a\":[2.5,false],\"c\":\"new\"}");
+	}
+
+	#[test]
+	fn compares_numbers_by_value() {
+		assert_eq!(Number::Int(42), Number::UInt(42));
+		assert_eq!(Number::Float(2.0), Number::Int(2));
+		assert_eq!(Number::UInt(u128::MAX), Number::UInt(u128::MAX));
+		assert_ne!(Number::Int(-1), Number::UInt(u128::MAX));
+		assert_ne!(Number::Float(2.5), Number::Int(2));
+		assert_ne!(Number::Float(i128::MAX as f64), Number::Int(i128::MAX));
+		assert_ne!(Number::Float(f64::NAN), Number::Float(f64::NAN));
+	}
+
+	#[test]
+	fn converts_into_value_directly() {
+		assert!(matches!(Value::from(2.0).to_value().unwrap(), Value::Number(Number::Float(value)) if value == 2.0));
+		assert!(matches!(42i8.to_value().unwrap(), Value::Number(Number::Int(42))));
+		assert!(matches!(42u8.to_value().unwrap(), Value::Number(Number::UInt(42))));
+
+		let value = Value::from(vec![Value::from(42), Value::from("x"), Value::Null]);
+		assert_eq!(value.to_value().unwrap(), value);
+
+		let mut map = alloc::collections::BTreeMap::new();
+		map.insert("list", vec![Some(1.5f32), None]);
+		assert_eq!(map.to_value().unwrap().to_json().unwrap(), "{\"list\":[1.5,null]}");
+
+		assert!(f64::NAN.to_value().is_err());
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn reads_values() {
+		let value = Value::from_json_str("{\"z\": [1, -1, 1.5], \"a\": {}}").unwrap();
+		assert_eq!(value.as_object().unwrap().keys().collect::<Vec<_>>(), vec!["z", "a"]);
+		assert!(matches!(value["z"][0], Value::Number(Number::UInt(1))));
+		assert!(matches!(value["z"][1], Value::Number(Number::Int(-1))));
+		assert_eq!(value["z"][2].as_number().unwrap().as_f64(), 1.5);
+		assert!(Value::from_json_str("{\"a\": 1, \"a\": 2}").is_err());
+		match Value::from_json_str("[1e400]") {
+			Err(crate::json_reader::ReadError::Value("number out of range", path)) => assert_eq!(path, "$[0]"),
+			other => panic!("unexpected result: {:?}", other)
+		}
+		assert!(Value::from_json_str("1e400").is_err());
+	}
+}
@@ -0,0 +1,55 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "derive")]
+extern crate json_builder_derive;
+
+mod json_path;
+mod number_format;
+mod pretty_config;
+mod color_scheme;
+mod sink;
+mod buffered_writer;
+mod inline_layout;
+#[macro_use]
+mod json_builder;
+mod json_display;
+mod drop_guard;
+mod typed_writer;
+mod scoped_writer;
+#[cfg(feature = "std")]
+mod json_reader;
+#[cfg(feature = "std")]
+#[macro_use]
+mod from_json;
+mod json_value;
+#[cfg(feature = "tokio")]
+mod async_builder;
+
+pub use json_path::*;
+pub use number_format::*;
+pub use pretty_config::*;
+pub use color_scheme::*;
+pub use sink::*;
+pub use json_builder::*;
+pub use json_display::*;
+pub use drop_guard::*;
+pub use typed_writer::*;
+pub use scoped_writer::*;
+#[cfg(feature = "std")]
+pub use json_reader::*;
+#[cfg(feature = "std")]
+pub use from_json::*;
+pub use json_value::*;
+#[cfg(feature = "tokio")]
+pub use async_builder::*;
+
+#[cfg(feature = "derive")]
+pub use json_builder_derive::IntoJSON;
+
+// used by the exported macros
+#[doc(hidden)]
+pub mod __private {
+	pub use alloc::string::String;
+}
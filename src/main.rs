@@ -1,12 +1,13 @@
 #![recursion_limit="128"]
 
 #[macro_use]
-mod json_builder;
+extern crate json_builder;
 
-use json_builder::{Result, JSONBuilder, IntoJSON, Error};
+use json_builder::{Result, JSONBuilder, IntoJSON, JSONReader, FromJSON, Value, json_display};
 
-const FOO: &'static str = "const FOO";
+const FOO: &str = "const FOO";
 
+#[derive(Default)]
 struct MyStruct {
 	hidden: String,
 	foo: i32,
@@ -36,10 +37,93 @@ impl_into_json! {
 	["\r".to_string().as_str()]: "\n"
 }
 
+// hidden isn't listed, so it's taken from MyStruct::default()
+impl_from_json! {
+	MyStruct [default],
+	foo, bar, baz, opt,
+	virtual_field: "...",
+	"with spaces": |this| this.foo - 44,
+	bla: |this| this.foo as usize + this.baz.len(),
+	x: {1 + 2},
+	y: 3 + 4,
+	z: |_this| {
+		println!("/* serializing MyStruct */");
+		None as Option<i32>
+	},
+	// use an expression as key
+	[FOO]: "FOO?",
+	["\r".to_string().as_str()]: "\n"
+}
+
 impl_into_json! {
 	TinyStruct, i
 }
 
+enum Shape {
+	Circle { radius: f64 },
+	Rectangle { width: f64, height: f64 },
+	Empty
+}
+
+impl_into_json! {
+	enum Shape [tag = "type"],
+	Circle { radius } as "circle",
+	Rectangle { width, height },
+	Empty
+}
+
+enum Event {
+	Click(i32, i32),
+	Key(char),
+	Resize { width: u32, height: u32 },
+	Close
+}
+
+impl_into_json! {
+	enum Event [tag = "t", content = "c"],
+	Click(x, y),
+	Key(key),
+	Resize { width, height } as "resize",
+	Close
+}
+
+enum Command {
+	Move(i32, i32),
+	Say(String),
+	Quit
+}
+
+impl_into_json! {
+	enum Command,
+	Move(x, y),
+	Say(text) as "say",
+	Quit
+}
+
+fn is_empty(value: &str) -> bool {
+	value.is_empty()
+}
+
+fn write_upper(value: &str, builder: &mut JSONBuilder) -> Result {
+	builder.value(value.to_uppercase())
+}
+
+#[derive(IntoJSON)]
+#[json(rename_all = "camelCase")]
+struct DerivedStruct<'a, T> where T: Copy {
+	first_name: &'a str,
+	#[json(rename = "surname")]
+	last_name: &'a str,
+	#[json(skip)]
+	#[allow(dead_code)]
+	password: &'a str,
+	#[json(skip_if = "is_empty")]
+	nick_name: &'a str,
+	#[json(with = "write_upper")]
+	home_town: &'a str,
+	extra_data: T
+}
+
 fn do_stuff() -> Result {
 	let mut out = std::io::stdout();
 	let mut b = JSONBuilder::new(&mut out);
@@ -66,7 +150,7 @@ fn do_stuff() -> Result {
 			b.value(123)?;
 			b.value(12.3)?;
 			b.value(optstr)?;
-			b.value(&multi)?;
+			b.value(multi)?;
 			b.value(&null)?;
 			b.value('x')?;
 			b.value("a string")?;
@@ -79,6 +163,62 @@ fn do_stuff() -> Result {
 	println!();
 	println!("{}", my_struct.to_json().ok().unwrap());
 	println!("{}", my_struct.to_pretty_json(3, false).ok().unwrap());
+	println!("{:#2}", json_display(&my_struct));
+
+	match MyStruct::from_json_str(&my_struct.to_json()?) {
+		Ok(parsed) => println!("parsed MyStruct: foo = {}, bar = {:?}, baz = {:?}, opt = {:?}", parsed.foo, parsed.bar, parsed.baz, parsed.opt),
+		Err(err) => println!("Error: {:?}", err)
+	}
+	match MyStruct::from_json_str(r#"{"foo": 1, "bar": "x", "baz": [true, 1], "opt": null}"#) {
+		Ok(_) => {},
+		Err(err) => println!("Error: {:?}", err)
+	}
+
+	let mut value = my_struct.to_value()?;
+	value["foo"] = Value::from(42);
+	value["baz"][1] = Value::from("changed");
+	println!("{}", value.to_json()?);
+
+	let derived = DerivedStruct {
+		first_name: "Jane",
+		last_name: "Doe",
+		password: "secret",
+		nick_name: "",
+		home_town: "Vienna",
+		extra_data: [1, 2, 3]
+	};
+	let derived_json = derived.to_json().ok().unwrap();
+	println!("{}", derived_json);
+
+	let mut reader = JSONReader::new(derived_json.as_bytes());
+	loop {
+		match reader.next_event() {
+			Ok(Some(event)) => println!("{:?}", event),
+			Ok(None) => break,
+			Err(err) => {
+				println!("Error: {:?}", err);
+				break;
+			}
+		}
+	}
+
+	let shapes = vec![
+		Shape::Circle { radius: 1.5 },
+		Shape::Rectangle { width: 2.0, height: 3.0 },
+		Shape::Empty
+	];
+	println!("{}", shapes.to_json().ok().unwrap());
+
+	let events = vec![
+		Event::Click(10, 20),
+		Event::Key('x'),
+		Event::Resize { width: 800, height: 600 },
+		Event::Close
+	];
+	println!("{}", events.to_json().ok().unwrap());
+
+	let commands = vec![Command::Move(1, -1), Command::Say("hello".to_string()), Command::Quit];
+	println!("{}", commands.to_json().ok().unwrap());
 
 	let s = "a string";
 	let i = 123;
@@ -126,9 +266,7 @@ fn do_stuff() -> Result {
 }
 
 fn main() {
-	match do_stuff() {
-		Err(Error::State(got, expected)) => println!("Error: illegal state: {:?}, expected one of: {:?}", got, expected),
-		Err(Error::IO(err)) => println!("Error: IO error: {}", err),
-		_ => {}
+	if let Err(err) = do_stuff() {
+		println!("Error: {}", err);
 	}
 }
@@ -0,0 +1,375 @@
+use core::fmt;
+
+use crate::json_builder::Result;
+use crate::sink::Sink;
+
+// Layout options for floats. The digits are always the shortest ones that
+// read back to the same value. The default matches the output of Display.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct FloatFormat {
+	// write 1.0 instead of 1, so readers can tell the value is a float
+	pub always_fraction: bool,
+	// use exponent notation for values >= 10^upper_exponent
+	pub upper_exponent: Option<i32>,
+	// use exponent notation for non-zero values < 10^lower_exponent
+	pub lower_exponent: Option<i32>
+}
+
+impl FloatFormat {
+	// the same thresholds as JavaScript's Number.prototype.toString()
+	pub fn javascript() -> FloatFormat {
+		FloatFormat {
+			always_fraction: false,
+			upper_exponent: Some(21),
+			lower_exponent: Some(-6)
+		}
+	}
+}
+
+// Enough for the shortest exponent form of any f64, e.g. "-2.2250738585072014e-308".
+const FLOAT_BUFFER_SIZE: usize = 32;
+
+struct StackBuffer {
+	bytes: [u8; FLOAT_BUFFER_SIZE],
+	len: usize
+}
+
+impl fmt::Write for StackBuffer {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let end = self.len + s.len();
+		if end > self.bytes.len() {
+			return Err(fmt::Error);
+		}
+		self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+		self.len = end;
+		Ok(())
+	}
+}
+
+const ZEROS: [u8; 64] = [b'0'; 64];
+
+fn write_zeros<W: Sink + ?Sized>(mut count: usize, writer: &mut W) -> Result {
+	while count > 0 {
+		let chunk = if count < ZEROS.len() { count } else { ZEROS.len() };
+		writer.write_bytes(&ZEROS[..chunk])?;
+		count -= chunk;
+	}
+	Ok(())
+}
+
+// Sealed like Integer, so write_float() only gets types whose LowerExp
+// output fits its stack buffer. Implemented for f32 and f64.
+pub trait Float: Copy + fmt::LowerExp + private::Sealed {}
+
+impl private::Sealed for f32 {}
+impl Float for f32 {}
+
+impl private::Sealed for f64 {}
+impl Float for f64 {}
+
+// Writes a finite float. The shortest round-trip digits are taken from the
+// LowerExp implementation of core, which are then laid out as configured.
+pub fn write_float<F: Float, W: Sink + ?Sized>(value: F, format: &FloatFormat, writer: &mut W) -> Result {
+	let mut buffer = StackBuffer { bytes: [0u8; FLOAT_BUFFER_SIZE], len: 0 };
+	// the buffer fits any f32 or f64
+	fmt::write(&mut buffer, format_args!("{:e}", value)).unwrap();
+	let formatted = &buffer.bytes[..buffer.len];
+
+	let (negative, formatted) = match formatted.first() {
+		Some(&b'-') => (true, &formatted[1..]),
+		_ => (false, formatted)
+	};
+	let e = formatted.iter().position(|&byte| byte == b'e').unwrap();
+	let exponent: i32 = core::str::from_utf8(&formatted[e + 1..]).unwrap().parse().unwrap();
+	let mantissa = &formatted[..e];
+
+	// mantissa is either "d" or "d.ddd"
+	let (first, rest) = if mantissa.len() > 1 {
+		(&mantissa[..1], &mantissa[2..])
+	} else {
+		(mantissa, &mantissa[1..])
+	};
+	let zero = first == b"0";
+
+	if negative {
+		writer.write_bytes(b"-")?;
+	}
+
+	let use_exponent = !zero && (
+		format.upper_exponent.is_some_and(|upper| exponent >= upper) ||
+		format.lower_exponent.is_some_and(|lower| exponent < lower)
+	);
+
+	if use_exponent {
+		writer.write_bytes(first)?;
+		if !rest.is_empty() {
+			writer.write_bytes(b".")?;
+			writer.write_bytes(rest)?;
+		} else if format.always_fraction {
+			writer.write_bytes(b".0")?;
+		}
+		writer.write_bytes(b"e")?;
+		return write_integer(exponent, writer);
+	}
+
+	let digit_count = 1 + rest.len() as i32;
+	// number of digits before the decimal point
+	let point = exponent + 1;
+
+	if point <= 0 {
+		writer.write_bytes(b"0.")?;
+		write_zeros((-point) as usize, writer)?;
+		writer.write_bytes(first)?;
+		writer.write_bytes(rest)?;
+	} else if point >= digit_count {
+		writer.write_bytes(first)?;
+		writer.write_bytes(rest)?;
+		write_zeros((point - digit_count) as usize, writer)?;
+		if format.always_fraction {
+			writer.write_bytes(b".0")?;
+		}
+	} else {
+		let split = (point - 1) as usize;
+		writer.write_bytes(first)?;
+		writer.write_bytes(&rest[..split])?;
+		writer.write_bytes(b".")?;
+		writer.write_bytes(&rest[split..])?;
+	}
+
+	Ok(())
+}
+
+// Largest integer is u128::MAX with 39 digits, or i128::MIN with 39 digits and a sign.
+// In hex it's at most 32 digits, a sign and 0x.
+const INTEGER_BUFFER_SIZE: usize = 40;
+
+const DIGIT_PAIRS: &[u8; 200] = b"\
+	0001020304050607080910111213141516171819\
+	2021222324252627282930313233343536373839\
+	4041424344454647484950515253545556575859\
+	6061626364656667686970717273747576777879\
+	8081828384858687888990919293949596979899";
+
+mod private {
+	pub trait Sealed {}
+}
+
+// Integers are written through a stack buffer with a digit pair lookup
+// table instead of the core::fmt machinery. Sealed, it's implemented for
+// all primitive integers and can't be implemented outside of this crate.
+pub trait Integer: Copy + private::Sealed {
+	// formats the integer right aligned into buffer, returns the start index
+	fn format(self, buffer: &mut [u8; INTEGER_BUFFER_SIZE]) -> usize;
+
+	// the same as a JSON5 hex literal, e.g. -0x1f
+	fn format_hex(self, buffer: &mut [u8; INTEGER_BUFFER_SIZE]) -> usize;
+}
+
+// Writes an integer in hex where the dialect allows it, see JSONBuilder::set_dialect().
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Hex<I>(pub I);
+
+pub(crate) static HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+fn write_pair(buffer: &mut [u8], pos: usize, pair: usize) {
+	buffer[pos]     = DIGIT_PAIRS[pair * 2];
+	buffer[pos + 1] = DIGIT_PAIRS[pair * 2 + 1];
+}
+
+fn format_u64(mut value: u64, buffer: &mut [u8], mut pos: usize) -> usize {
+	while value >= 10_000 {
+		let rem = (value % 10_000) as usize;
+		value /= 10_000;
+		pos -= 4;
+		write_pair(buffer, pos, rem / 100);
+		write_pair(buffer, pos + 2, rem % 100);
+	}
+
+	let mut value = value as usize;
+	if value >= 100 {
+		pos -= 2;
+		write_pair(buffer, pos, value % 100);
+		value /= 100;
+	}
+
+	if value >= 10 {
+		pos -= 2;
+		write_pair(buffer, pos, value);
+	} else {
+		pos -= 1;
+		buffer[pos] = b'0' + value as u8;
+	}
+
+	pos
+}
+
+fn format_u128(mut value: u128, buffer: &mut [u8], mut pos: usize) -> usize {
+	// split off chunks of 19 digits, the most that always fit into an u64
+	const CHUNK: u128 = 10_000_000_000_000_000_000;
+	while value > u64::MAX as u128 {
+		let rem = (value % CHUNK) as u64;
+		value /= CHUNK;
+		let start = format_u64(rem, buffer, pos);
+		let chunk_start = pos - 19;
+		for byte in &mut buffer[chunk_start..start] {
+			*byte = b'0';
+		}
+		pos = chunk_start;
+	}
+	format_u64(value as u64, buffer, pos)
+}
+
+macro_rules! impl_integer {
+	($format:ident as $wide:ty: $($t:ty),+) => {
+		$(impl private::Sealed for $t {}
+
+		impl Integer for $t {
+			#[allow(unused_comparisons, clippy::unnecessary_cast, clippy::cast_abs_to_unsigned)]
+			fn format(self, buffer: &mut [u8; INTEGER_BUFFER_SIZE]) -> usize {
+				let negative = self < 0;
+				let value = if negative {
+					(!(self as $wide)).wrapping_add(1)
+				} else {
+					self as $wide
+				};
+				let pos = $format(value, buffer, INTEGER_BUFFER_SIZE);
+				if negative {
+					buffer[pos - 1] = b'-';
+					pos - 1
+				} else {
+					pos
+				}
+			}
+
+			#[allow(unused_comparisons, clippy::unnecessary_cast, clippy::cast_abs_to_unsigned)]
+			fn format_hex(self, buffer: &mut [u8; INTEGER_BUFFER_SIZE]) -> usize {
+				let negative = self < 0;
+				let mut value = if negative {
+					(!(self as $wide)).wrapping_add(1)
+				} else {
+					self as $wide
+				};
+				let mut pos = INTEGER_BUFFER_SIZE;
+				loop {
+					pos -= 1;
+					buffer[pos] = HEX_DIGITS[(value & 0xF) as usize];
+					value >>= 4;
+					if value == 0 {
+						break;
+					}
+				}
+				pos -= 2;
+				buffer[pos] = b'0';
+				buffer[pos + 1] = b'x';
+				if negative {
+					pos -= 1;
+					buffer[pos] = b'-';
+				}
+				pos
+			}
+		})+
+	}
+}
+
+impl_integer!{ format_u64 as u64: i8, i16, i32, i64, isize, u8, u16, u32, u64, usize }
+impl_integer!{ format_u128 as u128: i128, u128 }
+
+pub fn write_integer<I: Integer, W: Sink + ?Sized>(value: I, writer: &mut W) -> Result {
+	let mut buffer = [0u8; INTEGER_BUFFER_SIZE];
+	let pos = value.format(&mut buffer);
+	writer.write_bytes(&buffer[pos..])
+}
+
+pub fn write_hex_integer<I: Integer, W: Sink + ?Sized>(value: I, writer: &mut W) -> Result {
+	let mut buffer = [0u8; INTEGER_BUFFER_SIZE];
+	let pos = value.format_hex(&mut buffer);
+	writer.write_bytes(&buffer[pos..])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::{String, ToString};
+	use alloc::vec::Vec;
+
+	fn float<F: Float>(value: F, format: &FloatFormat) -> String {
+		let mut output = Vec::new();
+		write_float(value, format, &mut output).unwrap();
+		String::from_utf8(output).unwrap()
+	}
+
+	#[test]
+	fn writes_floats_like_display() {
+		let values = [0.0, -0.0, 1.0, -1.5, 0.1, 1e-7, 123456.789, 1e21, 1e300, 5e-324, f64::MAX, f64::MIN_POSITIVE];
+		for &value in values.iter() {
+			assert_eq!(float(value, &FloatFormat::default()), value.to_string());
+		}
+		for &value in [0.1f32, 16777216.0, 3.4028235e38, 1e-45].iter() {
+			assert_eq!(float(value, &FloatFormat::default()), value.to_string());
+		}
+	}
+
+	fn integer<I: Integer>(value: I) -> String {
+		let mut output = Vec::new();
+		write_integer(value, &mut output).unwrap();
+		String::from_utf8(output).unwrap()
+	}
+
+	fn hex<I: Integer>(value: I) -> String {
+		let mut output = Vec::new();
+		write_hex_integer(value, &mut output).unwrap();
+		String::from_utf8(output).unwrap()
+	}
+
+	#[test]
+	fn writes_integers() {
+		assert_eq!(integer(0u8), "0");
+		assert_eq!(integer(i8::MIN), "-128");
+		assert_eq!(integer(u16::MAX), "65535");
+		assert_eq!(integer(-10_000i32), "-10000");
+		assert_eq!(integer(i64::MIN), i64::MIN.to_string());
+		assert_eq!(integer(u64::MAX), u64::MAX.to_string());
+		assert_eq!(integer(10_000_000_000_000_000_000u128), "10000000000000000000");
+		assert_eq!(integer(u128::MAX), u128::MAX.to_string());
+		assert_eq!(integer(i128::MIN), i128::MIN.to_string());
+		assert_eq!(integer(isize::MAX), isize::MAX.to_string());
+		for value in (-100_000i64..100_000).step_by(7) {
+			assert_eq!(integer(value), value.to_string());
+		}
+		let mut value = 1u128;
+		while let Some(next) = value.checked_mul(10) {
+			assert_eq!(integer(next - 1), (next - 1).to_string());
+			assert_eq!(integer(next), next.to_string());
+			value = next;
+		}
+	}
+
+	#[test]
+	fn writes_hex_integers() {
+		assert_eq!(hex(0u8), "0x0");
+		assert_eq!(hex(255u8), "0xff");
+		assert_eq!(hex(-31i32), "-0x1f");
+		assert_eq!(hex(i8::MIN), "-0x80");
+		assert_eq!(hex(u128::MAX), "0xffffffffffffffffffffffffffffffff");
+		assert_eq!(hex(i128::MIN), "-0x80000000000000000000000000000000");
+	}
+
+	#[test]
+	fn writes_floats_as_configured() {
+		let fraction = FloatFormat { always_fraction: true, ..FloatFormat::default() };
+		assert_eq!(float(1.0, &fraction), "1.0");
+		assert_eq!(float(-120.0, &fraction), "-120.0");
+		assert_eq!(float(0.5, &fraction), "0.5");
+
+		let javascript = FloatFormat::javascript();
+		assert_eq!(float(1e20, &javascript), "100000000000000000000");
+		assert_eq!(float(1e21, &javascript), "1e21");
+		assert_eq!(float(-1.5e-7, &javascript), "-1.5e-7");
+		assert_eq!(float(0.000001, &javascript), "0.000001");
+		assert_eq!(float(0.0, &javascript), "0");
+
+		let exponent = FloatFormat { always_fraction: true, upper_exponent: Some(3), lower_exponent: None };
+		assert_eq!(float(1000.0, &exponent), "1.0e3");
+		assert_eq!(float(999.5, &exponent), "999.5");
+	}
+}
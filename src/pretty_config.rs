@@ -0,0 +1,212 @@
+use alloc::string::String;
+
+use crate::json_builder::Result;
+use crate::sink::Sink;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum LineEnding {
+	#[default]
+	LF,
+	CRLF,
+	// everything on one line, without indentation
+	None
+}
+
+impl LineEnding {
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			LineEnding::LF => "\n",
+			LineEnding::CRLF => "\r\n",
+			LineEnding::None => ""
+		}
+	}
+}
+
+// How arrays and objects without elements are written.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum EmptyStyle {
+	// [] and {}
+	#[default]
+	Compact,
+	// [ ] and { }
+	Space,
+	// the closing bracket on its own line, like for non-empty containers,
+	// which also breaks the containers around it with a max_width
+	Expanded
+}
+
+// Layout of pretty printed output, e.g.:
+//
+//     PrettyConfig { trailing_newline: true, ..PrettyConfig::spaces(2) }
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrettyConfig {
+	// written once per nesting level
+	pub indent: String,
+	pub line_ending: LineEnding,
+	pub space_before_colon: bool,
+	pub space_after_colon: bool,
+	pub space_before_comma: bool,
+	// only where no line break follows the comma
+	pub space_after_comma: bool,
+	// a line ending after each complete top-level value
+	pub trailing_newline: bool,
+	pub empty_style: EmptyStyle,
+	// Arrays and objects that fit into this many columns are kept on one
+	// line, e.g. [1, 2, 3]. 0 breaks all of them. Output is held back until
+	// it's known whether a container fits, so only the output up to the
+	// start of an unfinished container reaches the writer.
+	pub max_width: usize,
+	// Pads after the colon so the values of an object line up. Objects are
+	// held back until they are closed for this, objects too large to be held
+	// back are written without aligned keys.
+	pub align_keys: bool,
+	// Keys more than this many columns longer than the shortest key of their
+	// object don't move the column of the values, they are just not padded.
+	pub max_key_padding: usize
+}
+
+impl PrettyConfig {
+	pub fn new(indent: &str) -> PrettyConfig {
+		PrettyConfig {
+			indent: indent.into(),
+			line_ending: LineEnding::LF,
+			space_before_colon: false,
+			space_after_colon: true,
+			space_before_comma: false,
+			space_after_comma: true,
+			trailing_newline: false,
+			empty_style: EmptyStyle::Compact,
+			max_width: 0,
+			align_keys: false,
+			max_key_padding: 16
+		}
+	}
+
+	pub fn spaces(count: usize) -> PrettyConfig {
+		PrettyConfig::new(&" ".repeat(count))
+	}
+
+	pub fn tabs(count: usize) -> PrettyConfig {
+		PrettyConfig::new(&"\t".repeat(count))
+	}
+
+	// [1, 2] and {"a": 1}
+	pub fn single_line() -> PrettyConfig {
+		PrettyConfig {
+			line_ending: LineEnding::None,
+			..PrettyConfig::new("")
+		}
+	}
+
+	// The config new_pretty() uses. An indent_size of 0 means compact
+	// output, for which there is no config.
+	pub fn from_indent(indent_size: usize, tab_indent: bool) -> Option<PrettyConfig> {
+		if indent_size == 0 {
+			None
+		} else if tab_indent {
+			Some(PrettyConfig::tabs(indent_size))
+		} else {
+			Some(PrettyConfig::spaces(indent_size))
+		}
+	}
+}
+
+impl Default for PrettyConfig {
+	fn default() -> PrettyConfig {
+		PrettyConfig::tabs(1)
+	}
+}
+
+// optimization to write up to 512 tabs/spaces at once (instead of byte-for-byte)
+const TABS:[u8; 512] = [9u8; 512];
+pub(crate) const SPACES:[u8; 512] = [32u8; 512];
+
+// writes a line ending and the indentation of depth levels
+pub(crate) fn write_line_break<W: Sink + ?Sized>(writer: &mut W, config: &PrettyConfig, depth: usize) -> Result {
+	writer.write_bytes(config.line_ending.as_str().as_bytes())?;
+
+	let indent = config.indent.as_bytes();
+	let fill = if indent.iter().all(|&byte| byte == b'\t') {
+		&TABS
+	} else if indent.iter().all(|&byte| byte == b' ') {
+		&SPACES
+	} else {
+		for _ in 0..depth {
+			writer.write_bytes(indent)?;
+		}
+		return Ok(());
+	};
+
+	let need = depth * indent.len();
+	let avail = fill.len();
+	if need < avail {
+		writer.write_bytes(&fill[..need])?;
+	} else {
+		let blocks = need / avail;
+		for _ in 0..blocks {
+			writer.write_bytes(fill)?;
+		}
+		writer.write_bytes(&fill[..(need - blocks * avail)])?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec::Vec;
+	use crate::json_builder::JSONBuilder;
+
+	fn pretty(config: PrettyConfig) -> String {
+		let mut builder = JSONBuilder::new_fmt_with_pretty_config(String::new(), config);
+		builder.begin_object().unwrap();
+		builder.item("a", &[1, 2][..]).unwrap();
+		builder.key("b").unwrap();
+		builder.begin_object().unwrap();
+		builder.end_object().unwrap();
+		builder.item("c", &[0u8; 0][..]).unwrap();
+		builder.end_object().unwrap();
+		builder.end().unwrap();
+		builder.into_inner().unwrap().into_inner()
+	}
+
+	#[test]
+	fn indents_and_breaks_lines() {
+		assert_eq!(pretty(PrettyConfig::default()), "{\n\t\"a\": [\n\t\t1,\n\t\t2\n\t],\n\t\"b\": {},\n\t\"c\": []\n}");
+		assert_eq!(pretty(PrettyConfig {
+			line_ending: LineEnding::CRLF,
+			trailing_newline: true,
+			..PrettyConfig::spaces(2)
+		}), "{\r\n  \"a\": [\r\n    1,\r\n    2\r\n  ],\r\n  \"b\": {},\r\n  \"c\": []\r\n}\r\n");
+		assert_eq!(pretty(PrettyConfig::new("-> ")), "{\n-> \"a\": [\n-> -> 1,\n-> -> 2\n-> ],\n-> \"b\": {},\n-> \"c\": []\n}");
+	}
+
+	#[test]
+	fn spaces_punctuation() {
+		assert_eq!(pretty(PrettyConfig::single_line()), "{\"a\": [1, 2], \"b\": {}, \"c\": []}");
+		assert_eq!(pretty(PrettyConfig {
+			space_before_colon: true,
+			space_after_colon: false,
+			space_before_comma: true,
+			space_after_comma: false,
+			..PrettyConfig::single_line()
+		}), "{\"a\" :[1 ,2] ,\"b\" :{} ,\"c\" :[]}");
+	}
+
+	#[test]
+	fn writes_empty_containers() {
+		assert_eq!(pretty(PrettyConfig { empty_style: EmptyStyle::Space, ..PrettyConfig::single_line() }), "{\"a\": [1, 2], \"b\": { }, \"c\": [ ]}");
+		assert_eq!(pretty(PrettyConfig { empty_style: EmptyStyle::Expanded, ..PrettyConfig::spaces(1) }), "{\n \"a\": [\n  1,\n  2\n ],\n \"b\": {\n },\n \"c\": [\n ]\n}");
+	}
+
+	#[test]
+	fn writes_deep_indentation() {
+		let mut output = Vec::new();
+		write_line_break(&mut output, &PrettyConfig::spaces(2), 300).unwrap();
+		assert_eq!(output.len(), 601);
+		assert!(output[1..].iter().all(|&byte| byte == b' '));
+		assert_eq!(PrettyConfig::from_indent(0, true), None);
+		assert_eq!(PrettyConfig::from_indent(2, true), Some(PrettyConfig::tabs(2)));
+	}
+}
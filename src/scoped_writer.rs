@@ -0,0 +1,144 @@
+use crate::json_builder::{IntoJSON, JSONBuilder, Result};
+use crate::sink::Sink;
+
+// Writers handed to the closures of JSONBuilder::object() and array(). The
+// container is closed when the closure returns, and if it returns an error
+// everything it left open is closed as well before the error is passed on.
+pub struct ObjectScope<'a, W: Sink> {
+	builder: &'a mut JSONBuilder<W>
+}
+
+pub struct ArrayScope<'a, W: Sink> {
+	builder: &'a mut JSONBuilder<W>
+}
+
+impl<W: Sink> JSONBuilder<W> {
+	pub fn object<F>(&mut self, f: F) -> Result where F: FnOnce(&mut ObjectScope<W>) -> Result {
+		let depth = self.depth();
+		self.begin_object()?;
+		match f(&mut ObjectScope { builder: self }) {
+			Ok(()) => self.end_object(),
+			Err(err) => {
+				let _ = self.close_to(depth);
+				Err(err)
+			}
+		}
+	}
+
+	pub fn array<F>(&mut self, f: F) -> Result where F: FnOnce(&mut ArrayScope<W>) -> Result {
+		let depth = self.depth();
+		self.begin_array()?;
+		match f(&mut ArrayScope { builder: self }) {
+			Ok(()) => self.end_array(),
+			Err(err) => {
+				let _ = self.close_to(depth);
+				Err(err)
+			}
+		}
+	}
+}
+
+impl<'a, W: Sink> ObjectScope<'a, W> {
+	pub fn item<Value: IntoJSON>(&mut self, key: &str, value: Value) -> Result {
+		self.builder.item(key, value)
+	}
+
+	pub fn object<F>(&mut self, key: &str, f: F) -> Result where F: FnOnce(&mut ObjectScope<W>) -> Result {
+		self.builder.key(key)?;
+		self.builder.object(f)
+	}
+
+	pub fn array<F>(&mut self, key: &str, f: F) -> Result where F: FnOnce(&mut ArrayScope<W>) -> Result {
+		self.builder.key(key)?;
+		self.builder.array(f)
+	}
+
+	// For code written against JSONBuilder, which has to leave it in the
+	// state it found it in.
+	pub fn builder(&mut self) -> &mut JSONBuilder<W> {
+		self.builder
+	}
+}
+
+impl<'a, W: Sink> ArrayScope<'a, W> {
+	pub fn value<Value: IntoJSON>(&mut self, value: Value) -> Result {
+		self.builder.value(value)
+	}
+
+	pub fn null(&mut self) -> Result {
+		self.builder.null()
+	}
+
+	pub fn object<F>(&mut self, f: F) -> Result where F: FnOnce(&mut ObjectScope<W>) -> Result {
+		self.builder.object(f)
+	}
+
+	pub fn array<F>(&mut self, f: F) -> Result where F: FnOnce(&mut ArrayScope<W>) -> Result {
+		self.builder.array(f)
+	}
+
+	// see ObjectScope::builder()
+	pub fn builder(&mut self) -> &mut JSONBuilder<W> {
+		self.builder
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec::Vec;
+	use alloc::string::ToString;
+	use crate::json_builder::{Error, State};
+
+	#[test]
+	fn closes_scopes() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.object(|o| {
+			o.item("id", 1)?;
+			o.array("tags", |a| {
+				a.value("a")?;
+				a.null()?;
+				a.object(|o| o.item("b", true))?;
+				a.array(|_| Ok(()))
+			})?;
+			o.object("meta", |o| {
+				o.builder().key("raw")?;
+				o.builder().value(2)
+			})
+		}).unwrap();
+		builder.end().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"{\"id\":1,\"tags\":[\"a\",null,{\"b\":true},[]],\"meta\":{\"raw\":2}}");
+	}
+
+	#[test]
+	fn closes_scopes_on_errors() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.begin_array().unwrap();
+		let err = builder.object(|o| {
+			o.array("list", |a| {
+				a.value(1)?;
+				a.builder().begin_object()?;
+				a.builder().key("open")?;
+				a.builder().key("twice")
+			})
+		}).unwrap_err();
+		match err {
+			Error::State { call: "key", state: State::ObjectValue, .. } => {},
+			other => panic!("unexpected error: {:?}", other)
+		}
+		assert_eq!(builder.depth(), 1);
+		assert_eq!(builder.path().to_string(), "$[1]");
+		builder.end_array().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"[{\"list\":[1,{\"open\":null}]}]");
+	}
+
+	#[test]
+	fn fails_outside_of_values() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.begin_object().unwrap();
+		assert!(builder.array(|a| a.value(1)).is_err());
+		assert_eq!(builder.depth(), 1);
+		builder.end_object().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"{}");
+	}
+}
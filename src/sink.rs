@@ -0,0 +1,184 @@
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::json_builder::{Error, Result};
+
+// Where the builder writes to. With the std feature every std::io::Write is
+// a Sink, without it Vec<u8> and &mut S are. SliceSink and &mut SliceSink
+// are Sinks either way.
+pub trait Sink {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result;
+
+	fn flush_sink(&mut self) -> Result {
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Sink for W {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		match self.write_all(bytes) {
+			Ok(()) => Ok(()),
+			Err(err) => Err(Error::IO(err))
+		}
+	}
+
+	fn flush_sink(&mut self) -> Result {
+		match self.flush() {
+			Ok(()) => Ok(()),
+			Err(err) => Err(Error::IO(err))
+		}
+	}
+}
+
+// type erased sinks, see DynJSONBuilder
+#[cfg(feature = "std")]
+impl<'a> Sink for &'a mut (dyn Sink + 'a) {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		(**self).write_bytes(bytes)
+	}
+
+	fn flush_sink(&mut self) -> Result {
+		(**self).flush_sink()
+	}
+}
+
+// the sink of a JSONBuilder without a type argument
+impl<'a> Sink for Box<dyn Sink + 'a> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		(**self).write_bytes(bytes)
+	}
+
+	fn flush_sink(&mut self) -> Result {
+		(**self).flush_sink()
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<S: Sink + ?Sized> Sink for &mut S {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		(**self).write_bytes(bytes)
+	}
+
+	fn flush_sink(&mut self) -> Result {
+		(**self).flush_sink()
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl Sink for Vec<u8> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		self.extend_from_slice(bytes);
+		Ok(())
+	}
+}
+
+// Writes into a fixed buffer. Fails with Error::BufferFull instead of
+// allocating, in which case nothing of the rejected write is kept.
+pub struct SliceSink<'a> {
+	buffer: &'a mut [u8],
+	len: usize
+}
+
+impl<'a> SliceSink<'a> {
+	pub fn new(buffer: &'a mut [u8]) -> SliceSink<'a> {
+		SliceSink { buffer, len: 0 }
+	}
+
+	// number of bytes written so far
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.buffer[..self.len]
+	}
+
+	pub fn into_bytes(self) -> &'a mut [u8] {
+		&mut self.buffer[..self.len]
+	}
+}
+
+impl<'a> Sink for SliceSink<'a> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		let end = self.len + bytes.len();
+		if end > self.buffer.len() {
+			return Err(Error::BufferFull);
+		}
+		self.buffer[self.len..end].copy_from_slice(bytes);
+		self.len = end;
+		Ok(())
+	}
+}
+
+// without std the impl for &mut S covers this
+#[cfg(feature = "std")]
+impl<'a, 'b> Sink for &'b mut SliceSink<'a> {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		(**self).write_bytes(bytes)
+	}
+}
+
+// Output collected behind a sink without a lifetime, for the boxed builders
+// of JSONBuilder::with_boxed() and IntoJSON::to_json(). The clone that is
+// kept takes the output once the builder is done.
+#[derive(Clone, Default)]
+pub(crate) struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+	pub(crate) fn take(&self) -> Vec<u8> {
+		core::mem::take(&mut *self.0.borrow_mut())
+	}
+}
+
+impl Sink for SharedOutput {
+	fn write_bytes(&mut self, bytes: &[u8]) -> Result {
+		self.0.borrow_mut().extend_from_slice(bytes);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::json_builder::JSONBuilder;
+
+	#[test]
+	fn writes_into_slices() {
+		let mut buffer = [0u8; 16];
+		let mut sink = SliceSink::new(&mut buffer);
+		{
+			let mut builder = JSONBuilder::new(&mut sink);
+			builder.value(&[1, 2][..]).unwrap();
+			builder.end().unwrap();
+		}
+		assert_eq!(sink.as_bytes(), b"[1,2]");
+		assert_eq!(sink.len(), 5);
+
+		let mut builder = JSONBuilder::new(sink);
+		match builder.value("0123456789") {
+			Err(Error::BufferFull) => {},
+			other => panic!("unexpected result: {:?}", other)
+		}
+		assert!(builder.into_inner().unwrap().into_bytes().starts_with(b"[1,2]"));
+	}
+
+	#[test]
+	fn rejects_writes_that_dont_fit() {
+		let mut buffer = [0u8; 4];
+		let mut sink = SliceSink::new(&mut buffer);
+		sink.write_bytes(b"abc").unwrap();
+		match sink.write_bytes(b"de") {
+			Err(Error::BufferFull) => {},
+			other => panic!("unexpected result: {:?}", other)
+		}
+		sink.write_bytes(b"d").unwrap();
+		assert_eq!(sink.into_bytes(), b"abcd");
+	}
+}
@@ -0,0 +1,165 @@
+use core::marker::PhantomData;
+
+use crate::json_builder::{Error, IntoJSON, JSONBuilder, Result};
+use crate::sink::Sink;
+
+// Typed front-end of JSONBuilder. Each writer only offers the calls that are
+// valid in its position, so e.g. key() inside an array doesn't compile:
+//
+//     let mut object = builder.typed().begin_object()?;
+//     object.item("id", 1)?;
+//     let mut tags = object.key("tags")?.begin_array()?;
+//     tags.value("a")?;
+//     tags.end()?.end()?;
+//
+// P is the writer that is returned once the current value is complete, ()
+// for the value the typed front-end was started with. Errors of the builder,
+// e.g. of a writer that was dropped without end(), are still reported at run
+// time by the builder.
+pub trait Parent<'a, W: Sink>: Sized {
+	fn resume(builder: &'a mut JSONBuilder<W>) -> Self;
+}
+
+impl<'a, W: Sink> Parent<'a, W> for () {
+	fn resume(_builder: &'a mut JSONBuilder<W>) {}
+}
+
+// A position where exactly one value has to be written.
+pub struct ValueWriter<'a, W: Sink, P> {
+	builder: &'a mut JSONBuilder<W>,
+	parent: PhantomData<P>
+}
+
+pub struct ArrayWriter<'a, W: Sink, P> {
+	builder: &'a mut JSONBuilder<W>,
+	parent: PhantomData<P>
+}
+
+pub struct ObjectWriter<'a, W: Sink, P> {
+	builder: &'a mut JSONBuilder<W>,
+	parent: PhantomData<P>
+}
+
+impl<W: Sink> JSONBuilder<W> {
+	pub fn typed(&mut self) -> ValueWriter<'_, W, ()> {
+		ValueWriter { builder: self, parent: PhantomData }
+	}
+}
+
+impl<'a, W: Sink, P: Parent<'a, W>> ValueWriter<'a, W, P> {
+	pub fn value<Value: IntoJSON>(self, value: Value) -> core::result::Result<P, Error> {
+		self.builder.value(value)?;
+		Ok(P::resume(self.builder))
+	}
+
+	pub fn null(self) -> core::result::Result<P, Error> {
+		self.builder.null()?;
+		Ok(P::resume(self.builder))
+	}
+
+	pub fn begin_array(self) -> core::result::Result<ArrayWriter<'a, W, P>, Error> {
+		self.builder.begin_array()?;
+		Ok(ArrayWriter { builder: self.builder, parent: PhantomData })
+	}
+
+	pub fn begin_object(self) -> core::result::Result<ObjectWriter<'a, W, P>, Error> {
+		self.builder.begin_object()?;
+		Ok(ObjectWriter { builder: self.builder, parent: PhantomData })
+	}
+}
+
+impl<'a, W: Sink, P: Parent<'a, W>> ArrayWriter<'a, W, P> {
+	pub fn value<Value: IntoJSON>(&mut self, value: Value) -> Result {
+		self.builder.value(value)
+	}
+
+	pub fn null(&mut self) -> Result {
+		self.builder.null()
+	}
+
+	pub fn begin_array(self) -> core::result::Result<ArrayWriter<'a, W, Self>, Error> {
+		self.builder.begin_array()?;
+		Ok(ArrayWriter { builder: self.builder, parent: PhantomData })
+	}
+
+	pub fn begin_object(self) -> core::result::Result<ObjectWriter<'a, W, Self>, Error> {
+		self.builder.begin_object()?;
+		Ok(ObjectWriter { builder: self.builder, parent: PhantomData })
+	}
+
+	pub fn end(self) -> core::result::Result<P, Error> {
+		self.builder.end_array()?;
+		Ok(P::resume(self.builder))
+	}
+}
+
+impl<'a, W: Sink, P: Parent<'a, W>> ObjectWriter<'a, W, P> {
+	pub fn key(self, key: &str) -> core::result::Result<ValueWriter<'a, W, Self>, Error> {
+		self.builder.key(key)?;
+		Ok(ValueWriter { builder: self.builder, parent: PhantomData })
+	}
+
+	pub fn item<Value: IntoJSON>(&mut self, key: &str, value: Value) -> Result {
+		self.builder.item(key, value)
+	}
+
+	pub fn end(self) -> core::result::Result<P, Error> {
+		self.builder.end_object()?;
+		Ok(P::resume(self.builder))
+	}
+}
+
+impl<'a, W: Sink, P: Parent<'a, W>> Parent<'a, W> for ArrayWriter<'a, W, P> {
+	fn resume(builder: &'a mut JSONBuilder<W>) -> Self {
+		ArrayWriter { builder, parent: PhantomData }
+	}
+}
+
+impl<'a, W: Sink, P: Parent<'a, W>> Parent<'a, W> for ObjectWriter<'a, W, P> {
+	fn resume(builder: &'a mut JSONBuilder<W>) -> Self {
+		ObjectWriter { builder, parent: PhantomData }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::vec::Vec;
+
+	#[test]
+	fn writes_nested_documents() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		let mut object = builder.typed().begin_object().unwrap();
+		object.item("id", 1).unwrap();
+		let mut tags = object.key("tags").unwrap().begin_array().unwrap();
+		tags.value("a").unwrap();
+		tags.null().unwrap();
+		let mut nested = tags.begin_object().unwrap().key("b").unwrap().begin_array().unwrap();
+		nested.value(true).unwrap();
+		let tags = nested.end().unwrap().end().unwrap();
+		let object = tags.end().unwrap();
+		let object = object.key("none").unwrap().null().unwrap();
+		let () = object.end().unwrap();
+		builder.end().unwrap();
+		assert_eq!(builder.into_inner().unwrap(), b"{\"id\":1,\"tags\":[\"a\",null,{\"b\":[true]}],\"none\":null}");
+	}
+
+	#[test]
+	fn writes_top_level_values() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		builder.typed().value("top").unwrap();
+		assert!(builder.typed().value(1).is_err());
+		assert_eq!(builder.into_inner().unwrap(), b"\"top\"");
+	}
+
+	#[test]
+	fn reports_unfinished_writers_at_run_time() {
+		let mut builder = JSONBuilder::new(Vec::new());
+		{
+			let mut array = builder.typed().begin_array().unwrap();
+			array.value(1).unwrap();
+		}
+		assert_eq!(builder.depth(), 1);
+		assert!(builder.end().is_err());
+	}
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "derive")]
+
+extern crate json_builder;
+
+use json_builder::{IntoJSON, JSONBuilder, Result};
+
+#[derive(IntoJSON)]
+#[json(rename_all = "camelCase")]
+struct Renamed {
+	first_name: &'static str,
+	_private_field: u8,
+	r#type: &'static str,
+	#[json(rename = "ID")]
+	id: u32
+}
+
+#[derive(IntoJSON)]
+#[json(rename_all = "PascalCase")]
+struct Pascal {
+	_private_field: u8,
+	r#type: u8
+}
+
+#[derive(IntoJSON)]
+struct Raw {
+	r#type: &'static str
+}
+
+fn write_upper(value: &str, builder: &mut JSONBuilder) -> Result {
+	builder.value(value.to_uppercase())
+}
+
+#[derive(IntoJSON)]
+struct Fields {
+	#[json(skip)]
+	_hidden: u8,
+	#[json(skip_if = "Option::is_none")]
+	maybe: Option<u8>,
+	#[json(skip_if = "Vec::is_empty")]
+	list: Vec<u8>,
+	#[json(with = "write_upper")]
+	name: String
+}
+
+#[derive(IntoJSON)]
+struct Newtype(u8);
+
+#[derive(IntoJSON)]
+struct NewtypeWith(#[json(with = "write_upper")] String);
+
+#[derive(IntoJSON)]
+struct Tuple(u8, #[json(skip)] (), #[json(skip_if = "str::is_empty")] &'static str, bool);
+
+#[derive(IntoJSON)]
+struct Unit;
+
+// not IntoJSON, which only skipped fields may have
+struct Opaque;
+
+#[derive(IntoJSON)]
+struct Generic<'a, T, U> {
+	items: &'a [T],
+	#[json(skip)]
+	_opaque: U
+}
+
+#[test]
+fn renames_fields() {
+	assert_eq!(Renamed { first_name: "a", _private_field: 1, r#type: "t", id: 2 }.to_json().unwrap(),
+		"{\"firstName\":\"a\",\"privateField\":1,\"type\":\"t\",\"ID\":2}");
+	assert_eq!(Pascal { _private_field: 1, r#type: 2 }.to_json().unwrap(), "{\"PrivateField\":1,\"Type\":2}");
+	assert_eq!(Raw { r#type: "t" }.to_json().unwrap(), "{\"type\":\"t\"}");
+}
+
+#[test]
+fn skips_fields_and_writes_with_functions() {
+	let fields = Fields { _hidden: 1, maybe: None, list: vec![], name: "a".to_string() };
+	assert_eq!(fields.to_json().unwrap(), "{\"name\":\"A\"}");
+	let fields = Fields { _hidden: 1, maybe: Some(2), list: vec![3], name: "b".to_string() };
+	assert_eq!(fields.to_json().unwrap(), "{\"maybe\":2,\"list\":[3],\"name\":\"B\"}");
+}
+
+#[test]
+fn writes_newtype_tuple_and_unit_structs() {
+	assert_eq!(Newtype(1).to_json().unwrap(), "1");
+	assert_eq!(NewtypeWith("a".to_string()).to_json().unwrap(), "\"A\"");
+	assert_eq!(Tuple(1, (), "", true).to_json().unwrap(), "[1,true]");
+	assert_eq!(Tuple(1, (), "x", false).to_json().unwrap(), "[1,\"x\",false]");
+	assert_eq!(Unit.to_json().unwrap(), "null");
+}
+
+#[test]
+fn writes_generic_structs() {
+	let generic = Generic { items: &[Newtype(1), Newtype(2)], _opaque: Opaque };
+	assert_eq!(generic.to_json().unwrap(), "{\"items\":[1,2]}");
+
+	// write_json() for a borrowed sink, including the with function
+	let mut output = Vec::new();
+	{
+		let mut builder = JSONBuilder::new(&mut output);
+		builder.begin_array().unwrap();
+		builder.value(&generic).unwrap();
+		builder.value(NewtypeWith("b".to_string())).unwrap();
+		builder.end_array().unwrap();
+	}
+	assert_eq!(output, b"[{\"items\":[1,2]},\"B\"]");
+}